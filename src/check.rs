@@ -0,0 +1,31 @@
+use std::error::Error;
+use std::path::Path;
+
+use structopt::StructOpt;
+
+use crate::terminal::Config;
+
+/// Validate the config without starting sila
+#[derive(StructOpt, Debug)]
+pub struct Check {}
+
+impl Check {
+    /// Print a report of the config, problems are returned as an error
+    pub fn run(&self, mut config: Config, path: &Path) -> Result<(), Box<dyn Error>> {
+        let problems = config.check();
+
+        println!("{}: {} terminals", path.display(), config.terminals.len());
+        for problem in problems.iter() {
+            println!("  - {}", problem);
+        }
+
+        match problems.len() {
+            0 => {
+                println!("no problems found");
+                Ok(())
+            }
+            1 => Err("1 problem found".into()),
+            count => Err(format!("{} problems found", count).into()),
+        }
+    }
+}
@@ -1,83 +1,287 @@
-#[derive(Debug, PartialEq, Clone)]
+use std::fmt;
+
+use crate::lexer::{tokenize, ParseError, RedirectOp, Token, Variables};
+
+#[derive(Debug, PartialEq, Clone, Default)]
 pub struct CommandOption {
     pub name: String,
     pub args: Vec<String>,
+
+    /// Applied in order, the same way a shell does
+    pub redirects: Vec<Redirect>,
 }
 
-impl CommandOption {
-    pub fn to_string(self) -> String {
-        format!("{} {}", self.name, self.args.join(" "))
-    }
-
-    pub fn new(input: String) -> Self {
-        let things = {
-            let mut iter = Vec::new();
-            let mut buffer = Vec::new();
-
-            for ch in input.trim().chars() {
-                if ch == ' ' {
-                    if !quotes_are_closed(&buffer) {
-                        buffer.push(ch);
-                    } else {
-                        // push the the result in the iterator and flush the buffer
-                        let word = buffer.iter().collect::<String>();
-                        iter.push(word);
-                        buffer = Vec::new();
-                    }
-                } else {
-                    buffer.push(ch);
-                }
-            }
+impl fmt::Display for CommandOption {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let words = std::iter::once(&self.name)
+            .chain(self.args.iter())
+            .map(|word| quote(word))
+            .chain(self.redirects.iter().map(|redirect| redirect.to_string()))
+            .collect::<Vec<_>>();
+
+        write!(f, "{}", words.join(" "))
+    }
+}
+
+/// Where the standard streams of a command go, file paths are relative to the terminal
+#[derive(Debug, PartialEq, Clone)]
+pub enum Redirect {
+    Stdin(String),
+    Stdout { path: String, append: bool },
+    Stderr { path: String, append: bool },
+    StderrToStdout,
+}
+
+impl Redirect {
+    fn new(op: RedirectOp, path: String) -> Self {
+        match op {
+            RedirectOp::Stdin => Redirect::Stdin(path),
+            RedirectOp::Stdout => Redirect::Stdout {
+                path,
+                append: false,
+            },
+            RedirectOp::AppendStdout => Redirect::Stdout { path, append: true },
+            RedirectOp::Stderr => Redirect::Stderr {
+                path,
+                append: false,
+            },
+            RedirectOp::AppendStderr => Redirect::Stderr { path, append: true },
+            RedirectOp::StderrToStdout => Redirect::StderrToStdout,
+        }
+    }
+}
 
-            if buffer.len() > 0 {
-                iter.push(buffer.iter().collect::<String>());
+impl fmt::Display for Redirect {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Redirect::Stdin(path) => write!(f, "< {}", quote(path)),
+            Redirect::Stdout { path, append } => {
+                write!(f, "{} {}", if *append { ">>" } else { ">" }, quote(path))
             }
+            Redirect::Stderr { path, append } => {
+                write!(f, "{} {}", if *append { "2>>" } else { "2>" }, quote(path))
+            }
+            Redirect::StderrToStdout => write!(f, "2>&1"),
+        }
+    }
+}
 
-            iter
-        };
+/// Commands connected with `|`
+#[derive(Debug, PartialEq, Clone)]
+pub struct Pipeline {
+    pub commands: Vec<CommandOption>,
+}
 
-        let name = things.first().unwrap().clone();
-        let args: Vec<String> = things.into_iter().skip(1).collect();
+impl fmt::Display for Pipeline {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let commands = self
+            .commands
+            .iter()
+            .map(|command| command.to_string())
+            .collect::<Vec<_>>();
 
-        CommandOption { name, args }
+        write!(f, "{}", commands.join(" | "))
     }
+}
 
-    pub fn from_input(input: String) -> Vec<CommandOption> {
-        let commands = if input.contains('|') {
-            input
-                .split('|')
-                .into_iter()
-                .map(|cmd| CommandOption::new(cmd.to_string()))
-                .collect::<Vec<CommandOption>>()
-        } else {
-            let mut vec = Vec::new();
-            vec.push(CommandOption::new(input));
+/// How a pipeline is chained to the previous one
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Connector {
+    /// `&&` runs the pipeline only if the previous one succeeded
+    And,
+    /// `||` runs the pipeline only if the previous one failed
+    Or,
+    /// `;` always runs the pipeline
+    Then,
+}
 
-            vec
-        };
+impl Connector {
+    pub fn should_run(&self, previous_succeeded: bool) -> bool {
+        match self {
+            Connector::And => previous_succeeded,
+            Connector::Or => !previous_succeeded,
+            Connector::Then => true,
+        }
+    }
 
-        commands
+    fn from_token(token: &Token) -> Option<Self> {
+        match token {
+            Token::And => Some(Connector::And),
+            Token::Or => Some(Connector::Or),
+            Token::Semicolon => Some(Connector::Then),
+            _ => None,
+        }
     }
+
+    fn symbol(&self) -> &'static str {
+        match self {
+            Connector::And => "&&",
+            Connector::Or => "||",
+            Connector::Then => ";",
+        }
+    }
+}
+
+/// A whole input line: pipelines joined by `&&`, `||` and `;`
+#[derive(Debug, PartialEq, Clone)]
+pub struct CommandLine {
+    pub first: Pipeline,
+    pub rest: Vec<(Connector, Pipeline)>,
+
+    /// The line as typed, kept to substitute the variables of every terminal
+    source: String,
 }
 
-fn quotes_are_closed(buffer: &Vec<char>) -> bool {
-    if buffer.contains(&'\'') && buffer.contains(&'"') {
-        return is_even(count_occurences(buffer, &'\'')) && is_even(count_occurences(buffer, &'"'));
-    } else if buffer.contains(&'\'') {
-        return is_even(count_occurences(buffer, &'\''));
-    } else if buffer.contains(&'"') {
-        return is_even(count_occurences(buffer, &'"'));
+impl CommandLine {
+    /// The command starting the line, used to recognize the helper commands
+    pub fn first_command(&self) -> &CommandOption {
+        &self.first.commands[0]
     }
 
-    true
+    /// Parse the line again replacing the variables in the names, arguments and redirections
+    pub fn expand(&self, variables: &Variables) -> Result<CommandLine, ParseError> {
+        CommandOption::parse(&self.source, Some(variables))
+    }
+}
+
+/// A line made of a single pipeline, its source is the quoted form
+/// of the commands so that nothing gets expanded
+impl From<Pipeline> for CommandLine {
+    fn from(pipeline: Pipeline) -> Self {
+        CommandLine {
+            source: pipeline.to_string(),
+            first: pipeline,
+            rest: vec![],
+        }
+    }
+}
+
+impl fmt::Display for CommandLine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.first)?;
+
+        for (connector, pipeline) in self.rest.iter() {
+            match connector {
+                Connector::Then => write!(f, "; {}", pipeline)?,
+                _ => write!(f, " {} {}", connector.symbol(), pipeline)?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl CommandOption {
+    /// Parse a line made of pipelines of commands separated by unquoted `|`,
+    /// the pipelines being joined by `&&`, `||` or `;`
+    pub fn from_input(input: String) -> Result<CommandLine, ParseError> {
+        CommandOption::parse(&input, None)
+    }
+
+    fn parse(input: &str, variables: Option<&Variables>) -> Result<CommandLine, ParseError> {
+        let mut segments: Vec<(Option<Connector>, Vec<Token>)> = vec![(None, vec![])];
+
+        for token in tokenize(input, variables)? {
+            match Connector::from_token(&token) {
+                Some(connector) => segments.push((Some(connector), vec![])),
+                None => segments.last_mut().unwrap().1.push(token),
+            }
+        }
+
+        // a trailing `;` is allowed as in `cargo fmt;`
+        if let Some((Some(Connector::Then), tokens)) = segments.last() {
+            if tokens.is_empty() {
+                segments.pop();
+            }
+        }
+
+        let connectors = segments
+            .iter()
+            .map(|(connector, _)| *connector)
+            .collect::<Vec<_>>();
+        let mut pipelines = vec![];
+
+        for (index, (connector, tokens)) in segments.into_iter().enumerate() {
+            if tokens.is_empty() && connectors.len() > 1 {
+                // blame the operator missing its left or right side
+                let operator = connector.or_else(|| connectors[index + 1]).unwrap();
+                return Err(ParseError::MissingCommand(operator.symbol().to_string()));
+            }
+
+            pipelines.push((
+                connector.unwrap_or(Connector::Then),
+                Pipeline::from_tokens(tokens)?,
+            ));
+        }
+
+        let mut pipelines = pipelines.into_iter();
+        let (_, first) = pipelines.next().unwrap();
+
+        Ok(CommandLine {
+            first,
+            rest: pipelines.collect(),
+            source: input.to_string(),
+        })
+    }
 }
 
-fn count_occurences(vec: &Vec<char>, c: &char) -> usize {
-    vec.iter().filter(|ch| *ch == c).count()
+impl Pipeline {
+    fn from_tokens(tokens: Vec<Token>) -> Result<Self, ParseError> {
+        if tokens.is_empty() {
+            return Err(ParseError::EmptyCommand);
+        }
+
+        let mut commands = vec![];
+        let mut words = vec![];
+        let mut redirects = vec![];
+        // `None` marks the end of the last command
+        let mut tokens = tokens.into_iter().map(Some).chain(std::iter::once(None));
+
+        while let Some(token) = tokens.next() {
+            match token {
+                Some(Token::Word(word)) => words.push(word),
+                Some(Token::Redirect(RedirectOp::StderrToStdout)) => {
+                    redirects.push(Redirect::StderrToStdout)
+                }
+                Some(Token::Redirect(op)) => match tokens.next() {
+                    Some(Some(Token::Word(path))) => redirects.push(Redirect::new(op, path)),
+                    _ => return Err(ParseError::MissingRedirectTarget(op.symbol().to_string())),
+                },
+                end_or_pipe => {
+                    let mut words = std::mem::take(&mut words).into_iter();
+                    let name = words.next().ok_or_else(|| {
+                        if end_or_pipe.is_none() && commands.is_empty() {
+                            ParseError::EmptyCommand
+                        } else {
+                            ParseError::MissingCommand("|".to_string())
+                        }
+                    })?;
+
+                    commands.push(CommandOption {
+                        name,
+                        args: words.collect(),
+                        redirects: std::mem::take(&mut redirects),
+                    });
+                }
+            }
+        }
+
+        Ok(Pipeline { commands })
+    }
 }
 
-fn is_even(nb: usize) -> bool {
-    nb % 2 == 0
+/// Quote a word back so that it can be displayed as it would be typed
+fn quote(word: &str) -> String {
+    let is_plain = !word.is_empty()
+        && word
+            .chars()
+            .all(|ch| ch.is_alphanumeric() || "-_./=:,@%+^~".contains(ch));
+
+    if is_plain {
+        word.to_string()
+    } else {
+        format!("'{}'", word.replace('\'', "'\\''"))
+    }
 }
 
 #[cfg(test)]
@@ -92,6 +296,7 @@ mod tests {
                 CommandOption {
                     name: "git".to_string(),
                     args: Vec::new(),
+                    ..Default::default()
                 },
             ),
             (
@@ -99,6 +304,7 @@ mod tests {
                 CommandOption {
                     name: "git".to_string(),
                     args: vec!["describe".to_string()],
+                    ..Default::default()
                 },
             ),
             (
@@ -109,8 +315,9 @@ mod tests {
                         "tag".to_string(),
                         "-a".to_string(),
                         "-m".to_string(),
-                        "'Some test'".to_string(),
+                        "Some test".to_string(),
                     ],
+                    ..Default::default()
                 },
             ),
             (
@@ -121,9 +328,10 @@ mod tests {
                         "tag".to_string(),
                         "-a".to_string(),
                         "-m".to_string(),
-                        "'Some test'".to_string(),
+                        "Some test".to_string(),
                         "--dry_run".to_string(),
                     ],
+                    ..Default::default()
                 },
             ),
             (
@@ -134,9 +342,10 @@ mod tests {
                         "tag".to_string(),
                         "-a".to_string(),
                         "-m".to_string(),
-                        r#""Some test""#.to_string(),
+                        "Some test".to_string(),
                         "--dry_run".to_string(),
                     ],
+                    ..Default::default()
                 },
             ),
             (
@@ -147,34 +356,192 @@ mod tests {
                         "tag".to_string(),
                         "-a".to_string(),
                         "-m".to_string(),
-                        r#""Some test 'appears here'""#.to_string(),
+                        "Some test 'appears here'".to_string(),
                         "--dry_run".to_string(),
                     ],
+                    ..Default::default()
                 },
             ),
             (
                 r#"ncu "/@conform\/batch.*/""#.to_string(),
                 CommandOption {
                     name: "ncu".to_string(),
-                    args: vec!["\"/@conform\\/batch.*/\"".to_string()],
+                    args: vec!["/@conform\\/batch.*/".to_string()],
+                    ..Default::default()
                 },
             ),
         ];
         for (input, output) in scenarios {
-            assert_eq!(CommandOption::new(input), output);
+            assert_eq!(
+                CommandOption::from_input(input).map(|line| line.first.commands),
+                Ok(vec![output])
+            );
         }
     }
 
     #[test]
-    fn check_should_continue_to_add() {
+    fn parse_pipelines() {
+        let commands = CommandOption::from_input("cat Cargo.toml | grep 'a|b' |wc -l".to_string());
+
+        assert_eq!(
+            commands.map(|line| {
+                line.first
+                    .commands
+                    .iter()
+                    .map(|c| c.to_string())
+                    .collect::<Vec<_>>()
+            }),
+            Ok(vec![
+                "cat Cargo.toml".to_string(),
+                "grep 'a|b'".to_string(),
+                "wc -l".to_string(),
+            ])
+        );
+    }
+
+    #[test]
+    fn parse_errors() {
         let scenarios = vec![
-            ("Some test '".chars().collect::<Vec<_>>(), false),
-            ("Some test 'test'".chars().collect::<Vec<_>>(), true),
-            (r#"Some test "test""#.chars().collect::<Vec<_>>(), true),
+            ("", ParseError::EmptyCommand),
+            ("  \t ", ParseError::EmptyCommand),
+            ("git log |", ParseError::MissingCommand("|".to_string())),
+            ("| grep a", ParseError::MissingCommand("|".to_string())),
+            ("git tag -m 'Some test", ParseError::UnterminatedQuote('\'')),
+            ("git fetch &&", ParseError::MissingCommand("&&".to_string())),
+            ("|| git fetch", ParseError::MissingCommand("||".to_string())),
+            (
+                "cargo fmt;; cargo clippy",
+                ParseError::Unsupported(";;".to_string()),
+            ),
+            (
+                "cargo fmt; ; cargo clippy",
+                ParseError::MissingCommand(";".to_string()),
+            ),
+            (
+                "git log | && wc",
+                ParseError::MissingCommand("|".to_string()),
+            ),
         ];
 
-        for (buffer, expected) in scenarios {
-            assert_eq!(quotes_are_closed(&buffer), expected);
+        for (input, expected) in scenarios {
+            assert_eq!(CommandOption::from_input(input.to_string()), Err(expected));
         }
     }
+
+    #[test]
+    fn display_quotes_arguments_back() {
+        let commands = CommandOption::from_input(r#"git tag -m "it's a test" """#.to_string());
+
+        assert_eq!(
+            commands.unwrap().first_command().to_string(),
+            r#"git tag -m 'it'\''s a test' ''"#
+        );
+    }
+
+    #[test]
+    fn parse_sequences() {
+        let line = CommandOption::from_input(
+            "git fetch && git rebase origin/main || echo failed; cargo fmt | head;".to_string(),
+        )
+        .unwrap();
+
+        assert_eq!(line.first.to_string(), "git fetch");
+        assert_eq!(
+            line.rest
+                .iter()
+                .map(|(connector, pipeline)| (*connector, pipeline.to_string()))
+                .collect::<Vec<_>>(),
+            vec![
+                (Connector::And, "git rebase origin/main".to_string()),
+                (Connector::Or, "echo failed".to_string()),
+                (Connector::Then, "cargo fmt | head".to_string()),
+            ]
+        );
+        assert_eq!(
+            line.to_string(),
+            "git fetch && git rebase origin/main || echo failed; cargo fmt | head"
+        );
+    }
+
+    #[test]
+    fn connectors_short_circuit() {
+        assert!(Connector::And.should_run(true));
+        assert!(!Connector::And.should_run(false));
+        assert!(!Connector::Or.should_run(true));
+        assert!(Connector::Or.should_run(false));
+        assert!(Connector::Then.should_run(false));
+    }
+
+    #[test]
+    fn parse_redirections() {
+        let line = CommandOption::from_input(
+            "jq . < data.json > 'out file.json' 2>&1 | tee -a log 2>> err".to_string(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            line.first.commands,
+            vec![
+                CommandOption {
+                    name: "jq".to_string(),
+                    args: vec![".".to_string()],
+                    redirects: vec![
+                        Redirect::Stdin("data.json".to_string()),
+                        Redirect::Stdout {
+                            path: "out file.json".to_string(),
+                            append: false,
+                        },
+                        Redirect::StderrToStdout,
+                    ],
+                },
+                CommandOption {
+                    name: "tee".to_string(),
+                    args: vec!["-a".to_string(), "log".to_string()],
+                    redirects: vec![Redirect::Stderr {
+                        path: "err".to_string(),
+                        append: true,
+                    }],
+                },
+            ]
+        );
+        assert_eq!(
+            line.to_string(),
+            "jq . < data.json > 'out file.json' 2>&1 | tee -a log 2>> err"
+        );
+
+        assert_eq!(
+            CommandOption::from_input("git log >".to_string()),
+            Err(ParseError::MissingRedirectTarget(">".to_string()))
+        );
+        assert_eq!(
+            CommandOption::from_input("git log > | wc".to_string()),
+            Err(ParseError::MissingRedirectTarget(">".to_string()))
+        );
+        assert_eq!(
+            CommandOption::from_input("> changes.txt".to_string()),
+            Err(ParseError::EmptyCommand)
+        );
+    }
+
+    #[test]
+    fn lines_built_from_commands_are_not_expanded() {
+        let pipeline = Pipeline {
+            commands: vec![CommandOption {
+                name: "sh".to_string(),
+                args: vec!["-c".to_string(), "echo $HOME {name} 'a'".to_string()],
+                ..Default::default()
+            }],
+        };
+        let mut variables = Variables::default();
+        variables
+            .env
+            .insert("HOME".to_string(), "/home/me".to_string());
+        variables
+            .placeholders
+            .insert("name".to_string(), "PJ1".to_string());
+
+        let line = CommandLine::from(pipeline.clone());
+
+        assert_eq!(line.expand(&variables).unwrap().first, pipeline);
+    }
 }
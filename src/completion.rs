@@ -0,0 +1,298 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use enum_iterator::IntoEnumIterator;
+use rustyline::{
+    completion::Completer, highlight::Highlighter, hint::Hinter, validate::Validator, Context,
+    Helper,
+};
+
+use crate::{ports::HelperCommand, runner::Runner};
+
+/// Helper commands taking terminal selectors as arguments
+static SELECTING: [&str; 4] = ["pin", "unpin", "ban", "unban"];
+
+/// Tab completion of the REPL, a snapshot of the runner refreshed after every line
+#[derive(Debug, Default)]
+pub struct Completion {
+    names: Vec<String>,
+    /// Tags and groups selected with `@name`
+    labels: Vec<String>,
+    /// Paths of the ordinary commands are completed from this directory
+    dir: Option<PathBuf>,
+}
+
+impl Completion {
+    pub fn new(runner: &Runner) -> Self {
+        let mut labels = runner
+            .all_terminals
+            .iter()
+            .flat_map(|terminal| terminal.tags.iter().cloned())
+            .chain(runner.groups.keys().cloned())
+            .collect::<Vec<_>>();
+        labels.sort();
+        labels.dedup();
+
+        Completion {
+            names: runner
+                .all_terminals
+                .iter()
+                .map(|t| t.name.clone())
+                .collect(),
+            labels,
+            dir: runner
+                .active_terminals()
+                .first()
+                .map(|terminal| PathBuf::from(&terminal.path)),
+        }
+    }
+
+    /// The start of the completed word and its candidates
+    fn candidates(&self, line: &str, pos: usize) -> (usize, Vec<String>) {
+        let line = &line[..pos];
+        let start = line
+            .char_indices()
+            .rev()
+            .find(|(_, ch)| ch.is_whitespace())
+            .map_or(0, |(index, ch)| index + ch.len_utf8());
+        let word = &line[start..];
+        let first_word = line.split_whitespace().next().unwrap_or_default();
+
+        // the `@PJ1,PJ3` target prefix and the word following it
+        if let Some(target) = first_word.strip_prefix('@') {
+            if start == 0 {
+                let from = target.rfind(',').map_or(1, |index| index + 2);
+                return (from, self.selectors(&line[from..]));
+            }
+
+            // helper commands can not be targeted
+            let rest = line.trim_start()[first_word.len()..].trim_start();
+            if !rest.contains(char::is_whitespace) {
+                return (start, vec![]);
+            }
+        }
+
+        if start == 0 {
+            return (start, self.commands(word));
+        }
+
+        if SELECTING.contains(&first_word) {
+            return (start, self.selectors(word));
+        }
+
+        // `remove` takes the exact terminal names only
+        if first_word == "remove" {
+            return (start, self.names(word));
+        }
+
+        match self.dir.as_ref() {
+            Some(dir) => (start, paths(dir, word)),
+            None => (start, vec![]),
+        }
+    }
+
+    fn commands(&self, prefix: &str) -> Vec<String> {
+        HelperCommand::into_enum_iter()
+            .map(|command| command.name().to_string())
+            .filter(|name| name.starts_with(prefix))
+            .collect()
+    }
+
+    fn names(&self, prefix: &str) -> Vec<String> {
+        self.names
+            .iter()
+            .filter(|name| name.starts_with(prefix))
+            .cloned()
+            .collect()
+    }
+
+    /// Terminal names, `@tag` and `@group`, keeping a leading `!`
+    fn selectors(&self, word: &str) -> Vec<String> {
+        let (negation, word) = match word.strip_prefix('!') {
+            Some(word) => ("!", word),
+            None => ("", word),
+        };
+
+        let candidates = match word.strip_prefix('@') {
+            Some(label) => self
+                .labels
+                .iter()
+                .filter(|name| name.starts_with(label))
+                .map(|name| format!("@{}", name))
+                .collect::<Vec<_>>(),
+            None => self.names(word),
+        };
+
+        candidates
+            .into_iter()
+            .map(|candidate| format!("{}{}", negation, candidate))
+            .collect()
+    }
+}
+
+/// Entries of the directory part of `word` starting with its file part,
+/// relative paths start from `dir` and the directories end with `/`
+fn paths(dir: &Path, word: &str) -> Vec<String> {
+    let (parent, prefix) = match word.rfind('/') {
+        Some(index) => (&word[..=index], &word[index + 1..]),
+        None => ("", word),
+    };
+    let unescaped = parent.replace("\\ ", " ");
+    let prefix = prefix.replace("\\ ", " ");
+
+    let entries = match fs::read_dir(dir.join(&unescaped)) {
+        Ok(entries) => entries,
+        Err(_) => return vec![],
+    };
+
+    let mut candidates = entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if !name.starts_with(&prefix) || (name.starts_with('.') && !prefix.starts_with('.')) {
+                return None;
+            }
+
+            let slash = match entry.path().is_dir() {
+                true => "/",
+                false => "",
+            };
+
+            Some(format!("{}{}{}", parent, name.replace(' ', "\\ "), slash))
+        })
+        .collect::<Vec<_>>();
+    candidates.sort();
+
+    candidates
+}
+
+impl Completer for Completion {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        Ok(self.candidates(line, pos))
+    }
+}
+
+impl Hinter for Completion {
+    type Hint = String;
+}
+
+impl Highlighter for Completion {}
+
+impl Validator for Completion {}
+
+impl Helper for Completion {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{terminal::Terminal, test_utils::TempDir};
+
+    fn completion(dir: Option<PathBuf>) -> Completion {
+        let terminal = |name: &str, tags: &[&str]| Terminal {
+            name: name.to_string(),
+            path: "/tmp".to_string(),
+            tags: tags.iter().map(|tag| tag.to_string()).collect(),
+            ..Default::default()
+        };
+        let mut runner = Runner::default();
+        runner.all_terminals = vec![
+            terminal("frontend-web", &["web"]),
+            terminal("frontend-admin", &["web", "admin"]),
+            terminal("backend", &[]),
+        ];
+        runner
+            .groups
+            .insert("servers".to_string(), vec!["backend".to_string()]);
+
+        Completion {
+            dir,
+            ..Completion::new(&runner)
+        }
+    }
+
+    fn complete(completion: &Completion, line: &str) -> (usize, Vec<String>) {
+        completion.candidates(line, line.len())
+    }
+
+    #[test]
+    fn test_complete_commands_and_selectors() {
+        let completion = completion(None);
+        let strings = |items: &[&str]| items.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+
+        let scenarios = vec![
+            ("un", (0, strings(&["unpin", "unban"]))),
+            (
+                "pin front",
+                (4, strings(&["frontend-web", "frontend-admin"])),
+            ),
+            (
+                "ban backend !fr",
+                (12, strings(&["!frontend-web", "!frontend-admin"])),
+            ),
+            ("unpin @", (6, strings(&["@admin", "@servers", "@web"]))),
+            ("@frontend-web,b", (14, strings(&["backend"]))),
+            ("@f", (1, strings(&["frontend-web", "frontend-admin"]))),
+            ("@web hi", (5, vec![])),
+            ("git status", (4, vec![])),
+            (
+                "remove front",
+                (7, strings(&["frontend-web", "frontend-admin"])),
+            ),
+            ("remove @", (7, vec![])),
+            ("remove !f", (7, vec![])),
+            (
+                "pin\u{a0}front",
+                (5, strings(&["frontend-web", "frontend-admin"])),
+            ),
+            ("\u{3000}@web hi", (8, vec![])),
+        ];
+
+        for (line, expected) in scenarios {
+            assert_eq!(complete(&completion, line), expected, "line: {}", line);
+        }
+    }
+
+    #[test]
+    fn test_complete_paths_from_the_first_active_terminal() {
+        let dir = TempDir::new("complete");
+        fs::create_dir_all(dir.join("src/bin")).unwrap();
+        fs::write(dir.join("src/main.rs"), "").unwrap();
+        fs::write(dir.join("src/my file.rs"), "").unwrap();
+        fs::write(dir.join(".hidden"), "").unwrap();
+        let completion = completion(Some(dir.to_path_buf()));
+
+        assert_eq!(
+            complete(&completion, "cat s"),
+            (4, vec!["src/".to_string()])
+        );
+        assert_eq!(
+            complete(&completion, "cat src/"),
+            (
+                4,
+                vec![
+                    "src/bin/".to_string(),
+                    "src/main.rs".to_string(),
+                    "src/my\\ file.rs".to_string()
+                ]
+            )
+        );
+        assert_eq!(
+            complete(&completion, "cat src/m"),
+            (
+                4,
+                vec!["src/main.rs".to_string(), "src/my\\ file.rs".to_string()]
+            )
+        );
+        assert_eq!(
+            complete(&completion, "cat ."),
+            (4, vec![".hidden".to_string()])
+        );
+    }
+}
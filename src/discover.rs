@@ -0,0 +1,235 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{selector, terminal::Terminal};
+
+/// Scan directory trees for projects and turn every one of them into a terminal
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct Discover {
+    /// Directories scanned for projects
+    pub roots: Vec<String>,
+
+    /// A directory containing one of these files or directories is a project
+    #[serde(default = "Discover::default_markers")]
+    pub markers: Vec<String>,
+
+    /// Globs on the directory name a project must match, all projects when empty
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub include: Vec<String>,
+
+    /// Globs on the directory name of the projects and directories to skip
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub exclude: Vec<String>,
+
+    /// How deep to look under the roots, the direct children are at depth 1
+    #[serde(default = "Discover::default_max_depth")]
+    pub max_depth: usize,
+}
+
+impl Discover {
+    fn default_markers() -> Vec<String> {
+        vec![".git".to_string()]
+    }
+
+    fn default_max_depth() -> usize {
+        2
+    }
+
+    /// The terminals of the projects found under the roots, named after their directory,
+    /// the path relative to the root is used for the names found more than once
+    pub fn terminals(&self) -> Vec<Terminal> {
+        let mut projects = vec![];
+
+        for root in self.roots.iter() {
+            let root = match PathBuf::from(root).canonicalize() {
+                Ok(root) => root,
+                Err(_) => continue,
+            };
+            self.scan(&root, &root, 0, &mut projects);
+        }
+
+        let mut seen = HashSet::new();
+        let duplicates = projects
+            .iter()
+            .filter(|(_, path)| !seen.insert(dir_name(path)))
+            .map(|(_, path)| dir_name(path))
+            .collect::<HashSet<_>>();
+
+        projects
+            .into_iter()
+            .map(|(root, path)| {
+                let name = match duplicates.contains(&dir_name(&path)) {
+                    true => path
+                        .strip_prefix(&root)
+                        .unwrap_or(&path)
+                        .to_string_lossy()
+                        .to_string(),
+                    false => dir_name(&path),
+                };
+
+                Terminal {
+                    name,
+                    path: path.to_string_lossy().to_string(),
+                    ..Default::default()
+                }
+            })
+            .collect()
+    }
+
+    /// Collect the projects under `dir`, a project is not scanned for nested projects
+    fn scan(&self, root: &Path, dir: &Path, depth: usize, projects: &mut Vec<(PathBuf, PathBuf)>) {
+        let name = dir_name(dir);
+
+        if depth > 0 && self.excluded(&name) {
+            return;
+        }
+
+        if self.markers.iter().any(|marker| dir.join(marker).exists()) {
+            if self.included(&name) {
+                projects.push((root.to_path_buf(), dir.to_path_buf()));
+            }
+            return;
+        }
+
+        if depth == self.max_depth {
+            return;
+        }
+
+        let mut children = match fs::read_dir(dir) {
+            Ok(entries) => entries
+                .filter_map(|entry| entry.ok())
+                .map(|entry| entry.path())
+                .filter(|path| path.is_dir() && !dir_name(path).starts_with('.'))
+                .collect::<Vec<_>>(),
+            Err(_) => return,
+        };
+        children.sort();
+
+        for child in children {
+            self.scan(root, &child, depth + 1, projects);
+        }
+    }
+
+    fn included(&self, name: &str) -> bool {
+        self.include.is_empty()
+            || self
+                .include
+                .iter()
+                .any(|glob| selector::matches_glob(glob, name))
+    }
+
+    fn excluded(&self, name: &str) -> bool {
+        self.exclude
+            .iter()
+            .any(|glob| selector::matches_glob(glob, name))
+    }
+}
+
+impl Default for Discover {
+    fn default() -> Self {
+        Discover {
+            roots: vec![],
+            markers: Discover::default_markers(),
+            include: vec![],
+            exclude: vec![],
+            max_depth: Discover::default_max_depth(),
+        }
+    }
+}
+
+fn dir_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::TempDir;
+
+    /// Create the directories with their marker files under a fresh temporary root
+    fn tree(name: &str, projects: &[&str]) -> TempDir {
+        let root = TempDir::new(name);
+
+        for project in projects {
+            let path = root.join(project);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+
+            if project.ends_with(".git") {
+                fs::create_dir_all(&path).unwrap();
+            } else {
+                fs::write(&path, "").unwrap();
+            }
+        }
+
+        root
+    }
+
+    fn names(terminals: Vec<Terminal>) -> Vec<String> {
+        terminals.into_iter().map(|t| t.name).collect()
+    }
+
+    #[test]
+    fn test_discover_projects_by_marker() {
+        let root = tree(
+            "markers",
+            &[
+                "api/.git",
+                "web/package.json",
+                "web/nested/.git",
+                "tools/cli/Cargo.toml",
+                "tools/deep/er/.git",
+                ".hidden/.git",
+                "docs/README.md",
+            ],
+        );
+        let discover = Discover {
+            roots: vec![root.to_string_lossy().to_string()],
+            markers: vec![
+                ".git".to_string(),
+                "Cargo.toml".to_string(),
+                "package.json".to_string(),
+            ],
+            ..Default::default()
+        };
+
+        let terminals = discover.terminals();
+        assert_eq!(names(terminals.clone()), vec!["api", "cli", "web"]);
+        assert_eq!(terminals[0].path, root.join("api").to_string_lossy());
+
+        let discover = Discover {
+            max_depth: 3,
+            ..discover
+        };
+        assert_eq!(names(discover.terminals()), vec!["api", "cli", "er", "web"]);
+    }
+
+    #[test]
+    fn test_discover_include_exclude_and_duplicates() {
+        let root = tree(
+            "filters",
+            &[
+                "svc-auth/.git",
+                "svc-legacy/.git",
+                "legacy/svc-billing/.git",
+                "client/api/.git",
+                "server/api/.git",
+            ],
+        );
+        let discover = Discover {
+            roots: vec![root.to_string_lossy().to_string()],
+            include: vec!["svc-*".to_string(), "api".to_string()],
+            exclude: vec!["*legacy".to_string()],
+            ..Default::default()
+        };
+
+        assert_eq!(
+            names(discover.terminals()),
+            vec!["client/api", "server/api", "svc-auth"]
+        );
+    }
+}
@@ -21,7 +21,7 @@ impl Help {
                 (
                     "pin",
                     "<term1> <term2>",
-                    "Pin one or multiple terminals separated by space. Following commands will run on top of pinned ones only."
+                    "Pin one or multiple terminals separated by space, accepts globs, /regex/, @group and !negation. Following commands will run on top of pinned ones only."
                 ),
                 HelperCommand::Unpin =>
                 (
@@ -33,7 +33,7 @@ impl Help {
                 (
                     "ban",
                     "<term1> <term2>",
-                    "Ban one or multiple terminals separated by space. The following commands will not run in banned terminals"
+                    "Ban one or multiple terminals separated by space, accepts globs, /regex/, @group and !negation. The following commands will not run in banned terminals"
                 ),
                 HelperCommand::Unban => 
                 (
@@ -42,6 +42,14 @@ impl Help {
                     "Unban the specificed terminals or all if no arguments provided."
                 ),
                 HelperCommand::List => ("list","","List the active terminal names."),
+                HelperCommand::Add => ("add", "<name> <path>", "Add a terminal until the application is closed or the config saved."),
+                HelperCommand::Remove => ("remove", "<name1> <name2>", "Remove the terminals until the config is reloaded."),
+                HelperCommand::Save => ("save", "[file]", "Write the current terminals to the file, the loaded config by default."),
+                HelperCommand::Stream => ("stream", "[on|off]", "Toggle between printing the output once a terminal finishes and streaming every line as it arrives."),
+                HelperCommand::Order => ("order", "[config|completion]", "Print the output in the config file order or as soon as every terminal finishes."),
+                HelperCommand::Shell => ("shell", "[on|off]", "Toggle running the lines through the configured shell instead of the built-in executor."),
+                HelperCommand::Reload => ("reload", "", "Load the config file again keeping the pinned and banned terminals."),
+                HelperCommand::History => ("history", "", "List the previous lines, !! runs the last one again and !n the n-th one."),
                 HelperCommand::Help => ("help", "", "Displays help information."),
                 HelperCommand::Exit => ("exit", "", "Close the application.")
             };
@@ -51,7 +59,7 @@ impl Help {
 
         commands_help
             .iter()
-            .for_each(|item| help_str.push_str(&format!("{0: <7} {1: <17} {2: <10}\n", item.0, item.1, item.2)));
+            .for_each(|item| help_str.push_str(&format!("{0: <7} {1: <20} {2: <10}\n", item.0, item.1, item.2)));
 
         help_str
     }
@@ -0,0 +1,127 @@
+use std::path::{Path, PathBuf};
+
+/// The history file of a config, every config keeps its own history under
+/// `$XDG_DATA_HOME/sila/history` or `~/.local/share/sila/history`
+pub fn file(config: &Path) -> Option<PathBuf> {
+    let data = match std::env::var_os("XDG_DATA_HOME") {
+        Some(data) if !data.is_empty() => PathBuf::from(data),
+        _ => PathBuf::from(std::env::var_os("HOME")?).join(".local/share"),
+    };
+    let config = std::path::absolute(config).ok()?;
+
+    // the config path is flattened so the file name tells which config it belongs to
+    let name = config.to_string_lossy().replace('/', "%");
+
+    Some(data.join("sila/history").join(name))
+}
+
+/// Replace the `!!` words with the previous line and `!n` with the n-th line of the history,
+/// like in a shell nothing is replaced between single quotes
+pub fn expand(line: &str, entries: &[String]) -> Result<String, String> {
+    let mut expanded = String::new();
+    let mut rest = line;
+    let mut double_quoted = false;
+
+    while let Some(start) = rest.find(['!', '\'', '"']) {
+        let quote = match rest.as_bytes()[start] {
+            b'"' => {
+                double_quoted = !double_quoted;
+                Some(start + 1)
+            }
+            b'\'' if double_quoted => Some(start + 1),
+            b'\'' => Some(
+                rest[start + 1..]
+                    .find('\'')
+                    .map_or(rest.len(), |end| start + end + 2),
+            ),
+            _ => None,
+        };
+        if let Some(end) = quote {
+            expanded.push_str(&rest[..end]);
+            rest = &rest[end..];
+            continue;
+        }
+
+        let after = &rest[start + 1..];
+        let before = match start {
+            0 => expanded.chars().last(),
+            _ => rest[..start].chars().last(),
+        };
+        let at_word_start = before.is_none_or(char::is_whitespace);
+        let end = after.find(char::is_whitespace).unwrap_or(after.len());
+        let word = &after[..end];
+
+        let entry = match word {
+            _ if !at_word_start => None,
+            "!" => Some(entries.last().ok_or("!!: event not found")?),
+            number if !number.is_empty() && number.chars().all(|ch| ch.is_ascii_digit()) => {
+                let entry = number
+                    .parse::<usize>()
+                    .ok()
+                    .and_then(|n| n.checked_sub(1))
+                    .and_then(|index| entries.get(index));
+
+                Some(entry.ok_or(format!("!{}: event not found", number))?)
+            }
+            _ => None,
+        };
+
+        match entry {
+            Some(entry) => {
+                expanded.push_str(&rest[..start]);
+                expanded.push_str(entry);
+                rest = &after[end..];
+            }
+            None => {
+                expanded.push_str(&rest[..start + 1]);
+                rest = after;
+            }
+        }
+    }
+
+    expanded.push_str(rest);
+    Ok(expanded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expand_history_references() {
+        let entries = vec!["git status".to_string(), "cargo test".to_string()];
+
+        let scenarios = vec![
+            ("!!", Ok("cargo test")),
+            ("!1", Ok("git status")),
+            ("!! -- --nocapture", Ok("cargo test -- --nocapture")),
+            ("echo !2", Ok("echo cargo test")),
+            ("pin !legacy-*", Ok("pin !legacy-*")),
+            ("echo hi!!", Ok("echo hi!!")),
+            (
+                "git commit -m 'fix !! now'",
+                Ok("git commit -m 'fix !! now'"),
+            ),
+            ("echo 'a' !1 'b !2", Ok("echo 'a' git status 'b !2")),
+            (r#"echo "it's" !!"#, Ok(r#"echo "it's" cargo test"#)),
+            ("!3", Err("!3: event not found")),
+            ("!0", Err("!0: event not found")),
+        ];
+
+        for (line, expected) in scenarios {
+            let expected = expected.map(String::from).map_err(String::from);
+            assert_eq!(expand(line, &entries), expected, "line: {}", line);
+        }
+
+        assert!(expand("!!", &[]).is_err());
+    }
+
+    #[test]
+    fn test_history_file_is_per_config() {
+        let first = file(Path::new("/work/a/sila.yaml"));
+        let second = file(Path::new("/work/b/sila.yaml"));
+
+        assert_ne!(first, second);
+        assert!(first.unwrap().ends_with("sila/history/%work%a%sila.yaml"));
+    }
+}
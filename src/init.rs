@@ -0,0 +1,150 @@
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use structopt::StructOpt;
+
+use crate::{
+    discover::Discover,
+    terminal::{Config, Terminal},
+};
+
+/// Generate a config file with a terminal for every project
+#[derive(StructOpt, Debug)]
+pub struct Init {
+    /// Directories to add as terminals, the subdirectories of the current directory by default
+    paths: Vec<PathBuf>,
+
+    #[structopt(long)]
+    /// Add the git repositories found under the paths instead of the paths themselves
+    repos: bool,
+
+    #[structopt(long)]
+    /// Overwrite the config file when it already exists
+    force: bool,
+}
+
+impl Init {
+    /// Write the config file at `path` and return the number of terminals in it
+    pub fn run(&self, path: &Path) -> Result<usize, Box<dyn Error>> {
+        if path.exists() && !self.force {
+            return Err(format!(
+                "{} already exists, use --force to overwrite it",
+                path.display()
+            )
+            .into());
+        }
+
+        let config = Config {
+            terminals: self.terminals()?,
+            ..Default::default()
+        };
+        config.save(path)?;
+
+        Ok(config.terminals.len())
+    }
+
+    fn terminals(&self) -> Result<Vec<Terminal>, Box<dyn Error>> {
+        let paths = match self.paths.is_empty() {
+            true => vec![PathBuf::from(".")],
+            false => self.paths.clone(),
+        };
+        let paths = paths
+            .iter()
+            .map(|path| {
+                path.canonicalize()
+                    .map_err(|e| format!("{}: {}", path.display(), e))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if self.repos {
+            let discover = Discover {
+                roots: paths
+                    .iter()
+                    .map(|path| path.to_string_lossy().to_string())
+                    .collect(),
+                ..Default::default()
+            };
+
+            return Ok(discover.terminals());
+        }
+
+        let dirs = match self.paths.is_empty() {
+            true => subdirectories(&paths[0])?,
+            false => paths,
+        };
+
+        Ok(dirs
+            .into_iter()
+            .map(|dir| Terminal {
+                name: dir
+                    .file_name()
+                    .map(|name| name.to_string_lossy().to_string())
+                    .unwrap_or_default(),
+                path: dir.to_string_lossy().to_string(),
+                ..Default::default()
+            })
+            .collect())
+    }
+}
+
+/// The visible subdirectories sorted by name
+fn subdirectories(dir: &Path) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let mut dirs = fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.is_dir())
+        .filter(|path| {
+            !path
+                .file_name()
+                .is_some_and(|name| name.to_string_lossy().starts_with('.'))
+        })
+        .collect::<Vec<_>>();
+    dirs.sort();
+
+    Ok(dirs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::TempDir;
+
+    #[test]
+    fn test_init_writes_a_loadable_config() {
+        let root = TempDir::new("init");
+        fs::create_dir_all(root.join("api/.git")).unwrap();
+        fs::create_dir_all(root.join("web")).unwrap();
+        let path = root.join("sila_config.yaml");
+
+        let init = Init {
+            paths: vec![root.join("web"), root.join("api")],
+            repos: false,
+            force: false,
+        };
+        assert_eq!(init.run(&path).unwrap(), 2);
+
+        let config = Config::load(&path).unwrap();
+        let names = config.terminals.iter().map(|t| t.name.as_str());
+        assert_eq!(names.collect::<Vec<_>>(), vec!["web", "api"]);
+        assert_eq!(
+            config.terminals[0].path,
+            root.join("web").canonicalize().unwrap().to_string_lossy()
+        );
+
+        // an existing file is kept unless forced
+        let init = Init {
+            paths: vec![root.to_path_buf()],
+            repos: true,
+            force: false,
+        };
+        assert!(init.run(&path).is_err());
+
+        let init = Init {
+            force: true,
+            ..init
+        };
+        assert_eq!(init.run(&path).unwrap(), 1);
+        assert_eq!(Config::load(&path).unwrap().terminals[0].name, "api");
+    }
+}
@@ -0,0 +1,57 @@
+use std::collections::HashSet;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc, Mutex,
+};
+
+/// The processes spawned by the running command lines, Ctrl-C interrupts
+/// all of them and stops the command lines from starting new ones
+#[derive(Debug, Clone, Default)]
+pub struct Children {
+    pids: Arc<Mutex<HashSet<u32>>>,
+    interrupted: Arc<AtomicBool>,
+}
+
+impl Children {
+    /// Track a spawned process, it is interrupted right away when Ctrl-C came first
+    pub fn add(&self, pid: u32) {
+        self.pids.lock().unwrap().insert(pid);
+
+        if self.is_interrupted() {
+            signal(pid);
+        }
+    }
+
+    /// Stop tracking a process once it is waited, its pid could be reused
+    pub fn remove(&self, pid: u32) {
+        self.pids.lock().unwrap().remove(&pid);
+    }
+
+    pub fn interrupt(&self) {
+        self.interrupted.store(true, Ordering::SeqCst);
+
+        for pid in self.pids.lock().unwrap().iter() {
+            signal(*pid);
+        }
+    }
+
+    pub fn is_interrupted(&self) -> bool {
+        self.interrupted.load(Ordering::SeqCst)
+    }
+
+    /// Forget a previous interruption before running the next line
+    pub fn reset(&self) {
+        self.interrupted.store(false, Ordering::SeqCst);
+    }
+}
+
+#[cfg(unix)]
+fn signal(pid: u32) {
+    // the result is ignored as the process may have exited in the meantime
+    unsafe {
+        libc::kill(pid as libc::pid_t, libc::SIGINT);
+    }
+}
+
+#[cfg(not(unix))]
+fn signal(_pid: u32) {}
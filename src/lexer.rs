@@ -0,0 +1,482 @@
+use std::{collections::HashMap, error::Error, fmt, iter::Peekable, str::Chars};
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Token {
+    Word(String),
+    Pipe,
+    And,
+    Or,
+    Semicolon,
+    Redirect(RedirectOp),
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum RedirectOp {
+    /// `<`
+    Stdin,
+    /// `>`
+    Stdout,
+    /// `>>`
+    AppendStdout,
+    /// `2>`
+    Stderr,
+    /// `2>>`
+    AppendStderr,
+    /// `2>&1`
+    StderrToStdout,
+}
+
+impl RedirectOp {
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            RedirectOp::Stdin => "<",
+            RedirectOp::Stdout => ">",
+            RedirectOp::AppendStdout => ">>",
+            RedirectOp::Stderr => "2>",
+            RedirectOp::AppendStderr => "2>>",
+            RedirectOp::StderrToStdout => "2>&1",
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum ParseError {
+    UnterminatedQuote(char),
+    TrailingBackslash,
+    /// An operator that is not surrounded by commands like in `| grep` or `git log |`
+    MissingCommand(String),
+    EmptyCommand,
+    Unsupported(String),
+    /// A redirection without the file it points to like in `git log >`
+    MissingRedirectTarget(String),
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::UnterminatedQuote(quote) => write!(f, "unterminated {} quote", quote),
+            ParseError::TrailingBackslash => write!(f, "nothing to escape after the trailing \\"),
+            ParseError::MissingCommand(operator) => {
+                write!(f, "missing command around `{}`", operator)
+            }
+            ParseError::EmptyCommand => write!(f, "empty command"),
+            ParseError::Unsupported(operator) => write!(f, "`{}` is not supported", operator),
+            ParseError::MissingRedirectTarget(operator) => {
+                write!(f, "missing file after `{}`", operator)
+            }
+        }
+    }
+}
+
+impl Error for ParseError {}
+
+/// Values substituted while the words are split
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Variables {
+    /// `{key}` placeholders, unknown keys are kept as they are
+    pub placeholders: HashMap<String, String>,
+
+    /// The environment used for `$VAR` and `${VAR}`, unknown variables are empty
+    pub env: HashMap<String, String>,
+}
+
+/// Split the input into shell words and operators following the POSIX quoting rules:
+/// single quotes keep everything literally, double quotes allow escaping `"`, `\`, `$` and `` ` ``,
+/// outside of quotes a backslash escapes any character.
+/// Without variables the placeholders and `$VAR` are kept as typed, otherwise they are replaced
+/// everywhere but in single quotes or when escaped, the values are never split in words
+pub fn tokenize(input: &str, variables: Option<&Variables>) -> Result<Vec<Token>, ParseError> {
+    let mut tokens = vec![];
+    let mut chars = input.chars().peekable();
+
+    // a word can be empty as in `''` so we track if one was started
+    let mut word: Option<String> = None;
+
+    while let Some(ch) = chars.next() {
+        match ch {
+            ' ' | '\t' | '\n' | '\r' => flush(&mut word, &mut tokens),
+            '|' | '&' | ';' => {
+                flush(&mut word, &mut tokens);
+                tokens.push(operator(ch, &mut chars)?);
+            }
+            '<' | '>' => {
+                flush(&mut word, &mut tokens);
+                tokens.push(Token::Redirect(redirect(ch, &mut chars)));
+            }
+            // `2>` only redirects stderr when the `2` is a word on its own
+            '2' if word.is_none() && chars.peek() == Some(&'>') => {
+                chars.next();
+                tokens.push(Token::Redirect(stderr_redirect(&mut chars)));
+            }
+            '\'' => {
+                let word = word.get_or_insert_with(String::new);
+                single_quoted(&mut chars, word)?;
+            }
+            '"' => {
+                let word = word.get_or_insert_with(String::new);
+                double_quoted(&mut chars, word, variables)?;
+            }
+            '{' | '$' => match variables.and_then(|variables| expand(ch, &mut chars, variables)) {
+                // an empty value outside of quotes does not produce a word, like in a shell
+                Some(value) if value.is_empty() => {}
+                Some(value) => word.get_or_insert_with(String::new).push_str(&value),
+                None => word.get_or_insert_with(String::new).push(ch),
+            },
+            '\\' => {
+                let escaped = chars.next().ok_or(ParseError::TrailingBackslash)?;
+                word.get_or_insert_with(String::new).push(escaped);
+            }
+            _ => word.get_or_insert_with(String::new).push(ch),
+        }
+    }
+
+    flush(&mut word, &mut tokens);
+
+    Ok(tokens)
+}
+
+fn flush(word: &mut Option<String>, tokens: &mut Vec<Token>) {
+    if let Some(word) = word.take() {
+        tokens.push(Token::Word(word));
+    }
+}
+
+fn operator(ch: char, chars: &mut Peekable<Chars>) -> Result<Token, ParseError> {
+    let doubled = chars.peek() == Some(&ch);
+    if doubled {
+        chars.next();
+    }
+
+    match (ch, doubled) {
+        ('|', false) => Ok(Token::Pipe),
+        ('|', true) => Ok(Token::Or),
+        ('&', true) => Ok(Token::And),
+        (';', false) => Ok(Token::Semicolon),
+        ('&', false) => Err(ParseError::Unsupported("&".to_string())),
+        _ => Err(ParseError::Unsupported(format!("{}{}", ch, ch))),
+    }
+}
+
+fn redirect(ch: char, chars: &mut Peekable<Chars>) -> RedirectOp {
+    if ch == '<' {
+        return RedirectOp::Stdin;
+    }
+
+    if chars.peek() == Some(&'>') {
+        chars.next();
+        return RedirectOp::AppendStdout;
+    }
+
+    RedirectOp::Stdout
+}
+
+fn stderr_redirect(chars: &mut Peekable<Chars>) -> RedirectOp {
+    if chars.peek() == Some(&'>') {
+        chars.next();
+        return RedirectOp::AppendStderr;
+    }
+
+    let mut lookahead = chars.clone();
+    if lookahead.next() == Some('&') && lookahead.next() == Some('1') {
+        chars.next();
+        chars.next();
+        return RedirectOp::StderrToStdout;
+    }
+
+    RedirectOp::Stderr
+}
+
+fn single_quoted(chars: &mut Peekable<Chars>, word: &mut String) -> Result<(), ParseError> {
+    for ch in chars {
+        if ch == '\'' {
+            return Ok(());
+        }
+        word.push(ch);
+    }
+
+    Err(ParseError::UnterminatedQuote('\''))
+}
+
+fn double_quoted(
+    chars: &mut Peekable<Chars>,
+    word: &mut String,
+    variables: Option<&Variables>,
+) -> Result<(), ParseError> {
+    while let Some(ch) = chars.next() {
+        match ch {
+            '"' => return Ok(()),
+            '{' | '$' => match variables.and_then(|variables| expand(ch, chars, variables)) {
+                Some(value) => word.push_str(&value),
+                None => word.push(ch),
+            },
+            '\\' => match chars.peek() {
+                Some('"') | Some('\\') | Some('$') | Some('`') => word.push(chars.next().unwrap()),
+                Some('\n') => {
+                    chars.next();
+                }
+                _ => word.push(ch),
+            },
+            _ => word.push(ch),
+        }
+    }
+
+    Err(ParseError::UnterminatedQuote('"'))
+}
+
+fn expand(ch: char, chars: &mut Peekable<Chars>, variables: &Variables) -> Option<String> {
+    if ch == '{' {
+        placeholder(chars, variables)
+    } else {
+        env_variable(chars, variables)
+    }
+}
+
+/// Consume a `NAME` or `{NAME}` following a `$`, anything else like `$1` or `$(` is kept
+fn env_variable(chars: &mut Peekable<Chars>, variables: &Variables) -> Option<String> {
+    let is_start = |ch: &char| ch.is_ascii_alphabetic() || *ch == '_';
+    let is_name = |ch: &char| ch.is_ascii_alphanumeric() || *ch == '_';
+
+    let name = if chars.peek() == Some(&'{') {
+        let name = chars
+            .clone()
+            .skip(1)
+            .take_while(|ch| *ch != '}')
+            .collect::<String>();
+        let is_valid =
+            name.chars().next().filter(is_start).is_some() && name.chars().all(|ch| is_name(&ch));
+
+        if !is_valid || chars.clone().nth(name.len() + 1) != Some('}') {
+            return None;
+        }
+
+        chars.nth(name.len() + 1);
+        name
+    } else {
+        chars.peek().filter(|ch| is_start(ch))?;
+
+        let mut name = String::new();
+        while let Some(ch) = chars.next_if(is_name) {
+            name.push(ch);
+        }
+        name
+    };
+
+    Some(variables.env.get(&name).cloned().unwrap_or_default())
+}
+
+/// Consume a `key}` following an opening brace if the key is a known placeholder
+fn placeholder(chars: &mut Peekable<Chars>, variables: &Variables) -> Option<String> {
+    let key = chars
+        .clone()
+        .take_while(|ch| *ch != '}')
+        .collect::<String>();
+    let is_key = key
+        .chars()
+        .all(|ch| ch.is_alphanumeric() || ch == '_' || ch == '-');
+
+    if !is_key || chars.clone().nth(key.chars().count()) != Some('}') {
+        return None;
+    }
+
+    let value = variables.placeholders.get(&key)?.clone();
+    chars.nth(key.chars().count());
+
+    Some(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(input: &str) -> Vec<String> {
+        tokenize(input, None)
+            .unwrap()
+            .into_iter()
+            .map(|token| match token {
+                Token::Word(word) => word,
+                token => panic!("unexpected token {:?}", token),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_tokenize_words() {
+        let scenarios = vec![
+            ("git  status", vec!["git", "status"]),
+            ("git\tstatus \t -s", vec!["git", "status", "-s"]),
+            ("echo 'Some test'", vec!["echo", "Some test"]),
+            (
+                r#"echo "Some 'nested' test""#,
+                vec!["echo", "Some 'nested' test"],
+            ),
+            (
+                r#"echo 'Some "nested" test'"#,
+                vec!["echo", r#"Some "nested" test"#],
+            ),
+            (
+                r#"git commit --msg="a b""#,
+                vec!["git", "commit", "--msg=a b"],
+            ),
+            (r#"echo 'a'"b"c"#, vec!["echo", "abc"]),
+            (r#"echo a\ b"#, vec!["echo", "a b"]),
+            (r#"echo "a \"b\" \c""#, vec!["echo", r#"a "b" \c"#]),
+            (r#"echo 'a\b'"#, vec!["echo", r#"a\b"#]),
+            ("echo '' \"\"", vec!["echo", "", ""]),
+        ];
+
+        for (input, expected) in scenarios {
+            assert_eq!(words(input), expected, "input: {}", input);
+        }
+    }
+
+    #[test]
+    fn test_tokenize_pipes_outside_quotes() {
+        assert_eq!(
+            tokenize("grep 'a|b' file|wc \\|", None).unwrap(),
+            vec![
+                Token::Word("grep".to_string()),
+                Token::Word("a|b".to_string()),
+                Token::Word("file".to_string()),
+                Token::Pipe,
+                Token::Word("wc".to_string()),
+                Token::Word("|".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tokenize_operators() {
+        assert_eq!(
+            tokenize("a&&b||c;d|e 'f&&g;'", None).unwrap(),
+            vec![
+                Token::Word("a".to_string()),
+                Token::And,
+                Token::Word("b".to_string()),
+                Token::Or,
+                Token::Word("c".to_string()),
+                Token::Semicolon,
+                Token::Word("d".to_string()),
+                Token::Pipe,
+                Token::Word("e".to_string()),
+                Token::Word("f&&g;".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tokenize_redirections() {
+        assert_eq!(
+            tokenize("jq . <in.json >out 2>&1 2>>err>>log 2> e a2>b '2>c'", None).unwrap(),
+            vec![
+                Token::Word("jq".to_string()),
+                Token::Word(".".to_string()),
+                Token::Redirect(RedirectOp::Stdin),
+                Token::Word("in.json".to_string()),
+                Token::Redirect(RedirectOp::Stdout),
+                Token::Word("out".to_string()),
+                Token::Redirect(RedirectOp::StderrToStdout),
+                Token::Redirect(RedirectOp::AppendStderr),
+                Token::Word("err".to_string()),
+                Token::Redirect(RedirectOp::AppendStdout),
+                Token::Word("log".to_string()),
+                Token::Redirect(RedirectOp::Stderr),
+                Token::Word("e".to_string()),
+                Token::Word("a2".to_string()),
+                Token::Redirect(RedirectOp::Stdout),
+                Token::Word("b".to_string()),
+                Token::Word("2>c".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tokenize_errors() {
+        let scenarios = vec![
+            ("echo 'Some test", ParseError::UnterminatedQuote('\'')),
+            (r#"echo "Some test"#, ParseError::UnterminatedQuote('"')),
+            (r#"echo "it's"'"#, ParseError::UnterminatedQuote('\'')),
+            ("echo test\\", ParseError::TrailingBackslash),
+            ("sleep 10 &", ParseError::Unsupported("&".to_string())),
+            ("echo a;; echo b", ParseError::Unsupported(";;".to_string())),
+        ];
+
+        for (input, expected) in scenarios {
+            assert_eq!(tokenize(input, None), Err(expected));
+        }
+    }
+
+    #[test]
+    fn test_tokenize_placeholders() {
+        let mut variables = Variables::default();
+        variables
+            .placeholders
+            .insert("name".to_string(), "PJ 1".to_string());
+        variables
+            .placeholders
+            .insert("empty".to_string(), "".to_string());
+
+        let input = r#"tar czf /b/{name}.tgz "{name}" '{name}' \{name} {nope} {} {name {empty}"#;
+        let expanded = tokenize(input, Some(&variables))
+            .unwrap()
+            .into_iter()
+            .map(|token| match token {
+                Token::Word(word) => word,
+                token => panic!("unexpected token {:?}", token),
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            expanded,
+            vec![
+                "tar",
+                "czf",
+                "/b/PJ 1.tgz",
+                "PJ 1",
+                "{name}",
+                "{name}",
+                "{nope}",
+                "{}",
+                "{name",
+            ]
+        );
+        assert_eq!(words(input).last().unwrap(), "{empty}");
+    }
+
+    #[test]
+    fn test_tokenize_env_variables() {
+        let mut variables = Variables::default();
+        variables
+            .env
+            .insert("HOME".to_string(), "/home/me".to_string());
+        variables.env.insert("A_1".to_string(), "a b".to_string());
+
+        let input =
+            r#"echo $HOME/x ${A_1}c "$A_1" '$HOME' \$HOME $1 $ ${bad $UNSET "$UNSET" a$A_1"#;
+        let expanded = tokenize(input, Some(&variables))
+            .unwrap()
+            .into_iter()
+            .map(|token| match token {
+                Token::Word(word) => word,
+                token => panic!("unexpected token {:?}", token),
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            expanded,
+            vec![
+                "echo",
+                "/home/me/x",
+                "a bc",
+                "a b",
+                "$HOME",
+                "$HOME",
+                "$1",
+                "$",
+                "${bad",
+                "",
+                "aa b",
+            ]
+        );
+        assert_eq!(words("echo $HOME"), vec!["echo", "$HOME"]);
+    }
+}
@@ -1,17 +1,30 @@
+use rustyline::{error::ReadlineError, history::FileHistory, Editor};
 use std::error::Error;
-use std::io::Write;
-use std::io::{stdin, stdout};
-use std::path::PathBuf;
+use std::fs::File;
+use std::io::{self, stdin, BufRead, BufReader, IsTerminal};
+use std::path::{Path, PathBuf};
 use structopt::StructOpt;
 
-use crate::command_option::CommandOption;
-use crate::ports::HelperCommand;
+use completion::Completion;
+use runner::{LineStatus, Runner};
+use terminal::ConfigSource;
 
+mod check;
 mod command_option;
+mod completion;
+mod discover;
 mod help;
+mod history;
+mod init;
+mod interrupt;
+mod lexer;
+mod output;
 mod ports;
 mod runner;
+mod selector;
 mod terminal;
+#[cfg(test)]
+mod test_utils;
 
 static VERSION: &str = "0.3.2";
 static ABOUT: &str = "A command line multiplexer.";
@@ -28,27 +41,172 @@ struct Opt {
     #[structopt(short, default_value = "./sila_config.yaml")]
     /// Provide the config file path
     path: PathBuf,
+
+    #[structopt(long)]
+    /// Scan the directory for projects and add them as terminals, can be repeated
+    discover: Vec<String>,
+
+    #[structopt(long)]
+    /// File or directory marking a project when discovering, defaults to .git
+    marker: Vec<String>,
+
+    #[structopt(long)]
+    /// Only discover the projects whose directory name matches the glob
+    include: Vec<String>,
+
+    #[structopt(long)]
+    /// Skip the directories whose name matches the glob when discovering
+    exclude: Vec<String>,
+
+    #[structopt(long)]
+    /// How deep to look for projects under the discovered directories
+    max_depth: Option<usize>,
+
+    #[structopt(long)]
+    /// Reload the config before running a line when the file changed
+    watch: bool,
+
+    #[structopt(short = "c", long = "command", conflicts_with = "script")]
+    /// Run a single line in the terminals and exit
+    line: Option<String>,
+
+    #[structopt(long)]
+    /// Run the lines of the file one after the other and exit
+    script: Option<PathBuf>,
+
+    #[structopt(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(StructOpt, Debug)]
+enum Command {
+    /// Generate the config file from a list of directories
+    Init(init::Init),
+    /// Validate the config file and report the problems
+    Check(check::Check),
+}
+
+impl Opt {
+    fn source(&self) -> ConfigSource {
+        ConfigSource {
+            path: self.path.clone(),
+            roots: self.discover.clone(),
+            markers: self.marker.clone(),
+            include: self.include.clone(),
+            exclude: self.exclude.clone(),
+            max_depth: self.max_depth,
+        }
+    }
 }
 
 fn run() -> Result<(), Box<dyn Error>> {
     let cli = Opt::from_args();
-    let mut runner = runner::Runner::new(cli.path);
+
+    match &cli.command {
+        Some(Command::Init(init)) => {
+            let count = init.run(&cli.path)?;
+            println!("Wrote {} terminals to {}", count, cli.path.display());
+
+            return Ok(());
+        }
+        Some(Command::Check(check)) => return check.run(cli.source().read()?, &cli.path),
+        None => {}
+    }
+
+    let mut runner = Runner::new(cli.source())?;
+    runner.watch = cli.watch;
+
+    // Ctrl-C while a line runs interrupts its processes instead of sila,
+    // at the prompt it is read by the line editor
+    let children = runner.children.clone();
+    ctrlc::set_handler(move || children.interrupt())?;
+
+    if let Some(line) = cli.line {
+        return run_batch(&mut runner, vec![Ok(line)].into_iter());
+    }
+    if let Some(script) = cli.script {
+        return run_batch(&mut runner, BufReader::new(File::open(script)?).lines());
+    }
+    if !stdin().is_terminal() {
+        // the script is on stdin, a command reading it would swallow the next lines
+        runner.detach_stdin = true;
+        return run_batch(&mut runner, stdin().lock().lines());
+    }
+
+    let mut editor = Editor::<Completion, FileHistory>::new()?;
+    editor.set_helper(Some(Completion::new(&runner)));
+    let history_file = history::file(&cli.path);
+    if let Some(file) = history_file.as_ref() {
+        // there is no history the first time a config is used
+        let _ = editor.load_history(file);
+        runner.history = editor.history().iter().cloned().collect();
+    }
 
     loop {
-        print!("> ");
-        stdout().flush().unwrap();
+        let line = match editor.readline(&runner.prompt()) {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => return Ok(()),
+            Err(e) => return Err(e.into()),
+        };
+
+        if let Some(line) = runner.recall(&line) {
+            if !line.is_empty() {
+                editor.add_history_entry(line.as_str())?;
+                save_history(&mut editor, history_file.as_deref());
+            }
+
+            if runner.execute_line(line) == LineStatus::Exit {
+                return Ok(());
+            }
+            editor.set_helper(Some(Completion::new(&runner)));
+        }
+    }
+}
 
-        let mut input = String::new();
-        stdin().read_line(&mut input)?;
-        let commands = CommandOption::from_input(input);
+/// Run the lines without a prompt, `#` starts a comment line,
+/// fails when any of the lines failed
+fn run_batch<I>(runner: &mut Runner, lines: I) -> Result<(), Box<dyn Error>>
+where
+    I: Iterator<Item = io::Result<String>>,
+{
+    let mut failed = 0;
+
+    for line in lines {
+        let line = line?;
+        if line.trim_start().starts_with('#') {
+            continue;
+        }
+
+        match runner.execute_line(line) {
+            LineStatus::Done => {}
+            LineStatus::Failed => failed += 1,
+            LineStatus::Exit => break,
+        }
+
+        // Ctrl-C stops the whole script
+        if runner.children.is_interrupted() {
+            break;
+        }
+    }
+
+    match failed {
+        0 => Ok(()),
+        1 => Err("1 line failed".into()),
+        count => Err(format!("{} lines failed", count).into()),
+    }
+}
 
-        let first_command = commands.first().unwrap().clone();
-        let helper_command_maybe = HelperCommand::to_enum(&first_command.name);
+fn save_history(editor: &mut Editor<Completion, FileHistory>, file: Option<&Path>) {
+    if let Some(file) = file {
+        let saved = file
+            .parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .map_err(ReadlineError::from)
+            .and_then(|_| editor.save_history(file));
 
-        if helper_command_maybe.is_some() {
-            runner.execute_helper_cmd(first_command)
-        } else {
-            runner.execute_cmds(commands);
+        if let Err(e) = saved {
+            println!("Could not save the history: {}", e);
         }
     }
 }
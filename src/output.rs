@@ -0,0 +1,309 @@
+use std::fmt;
+use std::io::{stdout, IsTerminal};
+#[cfg(unix)]
+use std::os::unix::process::ExitStatusExt;
+use std::process::ExitStatus;
+
+/// The result of running a command line in a single terminal
+#[derive(Debug)]
+pub struct Output {
+    pub terminal_name: String,
+    pub command: String,
+    pub stdout: Vec<u8>,
+
+    /// stderr is captured separately for every stage of the executed pipelines
+    pub stages: Vec<StageOutput>,
+
+    /// Status of the last executed pipeline
+    pub status: Status,
+}
+
+/// What a single stage of a pipeline wrote on its stderr and how it ended
+#[derive(Debug)]
+pub struct StageOutput {
+    pub name: String,
+    pub stderr: Vec<u8>,
+    pub status: Status,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Exited(i32),
+    Signaled(i32),
+    /// The process could not be spawned at all
+    NotStarted,
+}
+
+impl Status {
+    pub fn success(&self) -> bool {
+        *self == Status::Exited(0)
+    }
+
+    /// Status of a pipeline, the rightmost failing stage wins like in `set -o pipefail`.
+    /// A stage killed by SIGPIPE before the last one is not a failure, the next stage
+    /// only stopped reading like `head` does
+    pub fn of_pipeline(stages: &[StageOutput]) -> Status {
+        let last = stages.len().saturating_sub(1);
+
+        stages
+            .iter()
+            .enumerate()
+            .rev()
+            .filter(|(index, stage)| {
+                *index == last || stage.status != Status::Signaled(libc::SIGPIPE)
+            })
+            .map(|(_, stage)| stage.status)
+            .find(|status| !status.success())
+            .unwrap_or(Status::Exited(0))
+    }
+}
+
+impl From<ExitStatus> for Status {
+    fn from(status: ExitStatus) -> Self {
+        if let Some(code) = status.code() {
+            return Status::Exited(code);
+        }
+
+        #[cfg(unix)]
+        if let Some(signal) = status.signal() {
+            return Status::Signaled(signal);
+        }
+
+        Status::NotStarted
+    }
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Status::Exited(code) => write!(f, "exit {}", code),
+            Status::Signaled(signal) => write!(f, "signal {}", signal),
+            Status::NotStarted => write!(f, "not started"),
+        }
+    }
+}
+
+impl Output {
+    /// Render the output under the `[NAME]> command` header,
+    /// stderr lines are prefixed with the stage name and colored in red
+    pub fn render(&self, colored: bool) -> String {
+        let mut rendered = format!("[{}]> {}\n", self.terminal_name, self.command);
+        rendered.push_str(&String::from_utf8_lossy(&self.stdout));
+
+        for stage in self.stages.iter() {
+            for line in String::from_utf8_lossy(&stage.stderr).lines() {
+                let line = format!("{}! {}", stage.name, line);
+                rendered.push_str(&paint(&line, RED, colored));
+                rendered.push('\n');
+            }
+        }
+
+        rendered
+    }
+}
+
+/// Aggregated statuses of all the terminals a command ran into
+#[derive(Debug, Default)]
+pub struct Summary {
+    pub ok: usize,
+    pub failed: Vec<(String, Status)>,
+}
+
+impl Summary {
+    pub fn add(&mut self, output: &Output) {
+        let status = output.status;
+
+        if status.success() {
+            self.ok += 1;
+        } else {
+            self.failed.push((output.terminal_name.clone(), status));
+        }
+    }
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ok, {} failed", self.ok, self.failed.len())?;
+
+        if !self.failed.is_empty() {
+            let failures = self
+                .failed
+                .iter()
+                .map(|(name, status)| format!("{}: {}", name, status))
+                .collect::<Vec<_>>();
+
+            write!(f, " ({})", failures.join(", "))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Prefix printed in front of every streamed line of a terminal
+#[derive(Debug, Clone)]
+pub struct Tag {
+    label: String,
+    colored: bool,
+}
+
+impl Tag {
+    /// The color of the tag is picked by the terminal position
+    pub fn new(terminal_name: &str, index: usize, colored: bool) -> Self {
+        let color = TAG_COLORS[index % TAG_COLORS.len()];
+
+        Tag {
+            label: paint(&format!("[{}]", terminal_name), color, colored),
+            colored,
+        }
+    }
+
+    pub fn stdout_line(&self, line: &str) -> String {
+        format!("{} {}", self.label, line)
+    }
+
+    pub fn stderr_line(&self, stage: &str, line: &str) -> String {
+        let line = format!("{}! {}", stage, line);
+
+        format!("{} {}", self.label, paint(&line, RED, self.colored))
+    }
+}
+
+static RED: &str = "31";
+
+/// Red is left out as it is reserved for stderr
+static TAG_COLORS: [&str; 5] = ["32", "33", "34", "35", "36"];
+
+/// Check if the output supports colors, respecting the `NO_COLOR` convention
+pub fn use_colors() -> bool {
+    stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none()
+}
+
+fn paint(text: &str, color: &str, colored: bool) -> String {
+    if colored {
+        format!("\x1b[{}m{}\x1b[0m", color, text)
+    } else {
+        text.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_attributes_stderr_to_stages() {
+        let output = Output {
+            terminal_name: "PJ1".to_string(),
+            command: "cargo build | tee log".to_string(),
+            stdout: b"done\n".to_vec(),
+            stages: vec![
+                StageOutput {
+                    name: "cargo".to_string(),
+                    stderr: b"error[E0425]: oops\n".to_vec(),
+                    status: Status::Exited(101),
+                },
+                StageOutput {
+                    name: "tee".to_string(),
+                    stderr: vec![],
+                    status: Status::Exited(0),
+                },
+            ],
+            status: Status::Exited(101),
+        };
+
+        assert_eq!(
+            output.render(false),
+            "[PJ1]> cargo build | tee log\ndone\ncargo! error[E0425]: oops\n"
+        );
+        assert!(output
+            .render(true)
+            .contains("\x1b[31mcargo! error[E0425]: oops\x1b[0m"));
+    }
+
+    fn stages(statuses: Vec<Status>) -> Vec<StageOutput> {
+        statuses
+            .into_iter()
+            .map(|status| StageOutput {
+                name: "cmd".to_string(),
+                stderr: vec![],
+                status,
+            })
+            .collect()
+    }
+
+    fn output(name: &str, status: Status) -> Output {
+        Output {
+            terminal_name: name.to_string(),
+            command: "cmd".to_string(),
+            stdout: vec![],
+            stages: stages(vec![status]),
+            status,
+        }
+    }
+
+    #[test]
+    fn test_pipeline_status_is_the_rightmost_failure() {
+        let scenarios = vec![
+            (
+                vec![Status::Exited(0), Status::Exited(0)],
+                Status::Exited(0),
+            ),
+            (
+                vec![Status::Exited(2), Status::Exited(0)],
+                Status::Exited(2),
+            ),
+            (
+                vec![Status::Exited(2), Status::Signaled(9), Status::Exited(0)],
+                Status::Signaled(9),
+            ),
+            (
+                vec![Status::Exited(0), Status::NotStarted],
+                Status::NotStarted,
+            ),
+            (
+                vec![Status::Signaled(libc::SIGPIPE), Status::Exited(0)],
+                Status::Exited(0),
+            ),
+            (
+                vec![Status::Signaled(libc::SIGPIPE), Status::Exited(3)],
+                Status::Exited(3),
+            ),
+            (
+                vec![Status::Exited(0), Status::Signaled(libc::SIGPIPE)],
+                Status::Signaled(libc::SIGPIPE),
+            ),
+        ];
+
+        for (statuses, expected) in scenarios {
+            assert_eq!(Status::of_pipeline(&stages(statuses)), expected);
+        }
+    }
+
+    #[test]
+    fn test_summary_lists_failed_terminals() {
+        let mut summary = Summary::default();
+        summary.add(&output("PJ1", Status::Exited(0)));
+        summary.add(&output("PJ3", Status::Exited(101)));
+        summary.add(&output("PJ7", Status::Signaled(9)));
+
+        assert_eq!(
+            summary.to_string(),
+            "1 ok, 2 failed (PJ3: exit 101, PJ7: signal 9)"
+        );
+
+        let mut summary = Summary::default();
+        summary.add(&output("PJ1", Status::Exited(0)));
+        assert_eq!(summary.to_string(), "1 ok, 0 failed");
+    }
+
+    #[test]
+    fn test_tag_prefixes_streamed_lines() {
+        let tag = Tag::new("PJ1", 0, false);
+
+        assert_eq!(tag.stdout_line("Compiling sila"), "[PJ1] Compiling sila");
+        assert_eq!(tag.stderr_line("cargo", "warning"), "[PJ1] cargo! warning");
+
+        let tag = Tag::new("PJ2", 6, true);
+        assert_eq!(tag.stdout_line("ok"), "\x1b[33m[PJ2]\x1b[0m ok");
+    }
+}
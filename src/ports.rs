@@ -7,6 +7,14 @@ pub enum HelperCommand {
     Ban,
     Unban,
     List,
+    Add,
+    Remove,
+    Save,
+    Stream,
+    Order,
+    Shell,
+    Reload,
+    History,
     Help,
     Exit,
 }
@@ -19,12 +27,41 @@ impl HelperCommand {
             "ban" => Some(HelperCommand::Ban),
             "unban" => Some(HelperCommand::Unban),
             "list" => Some(HelperCommand::List),
+            "add" => Some(HelperCommand::Add),
+            "remove" => Some(HelperCommand::Remove),
+            "save" => Some(HelperCommand::Save),
+            "stream" => Some(HelperCommand::Stream),
+            "order" => Some(HelperCommand::Order),
+            "shell" => Some(HelperCommand::Shell),
+            "reload" => Some(HelperCommand::Reload),
+            "history" => Some(HelperCommand::History),
             "help" => Some(HelperCommand::Help),
             "exit" => Some(HelperCommand::Exit),
 
             _ => None,
         }
     }
+
+    /// The name the command is typed with
+    pub fn name(&self) -> &'static str {
+        match self {
+            HelperCommand::Pin => "pin",
+            HelperCommand::Unpin => "unpin",
+            HelperCommand::Ban => "ban",
+            HelperCommand::Unban => "unban",
+            HelperCommand::List => "list",
+            HelperCommand::Add => "add",
+            HelperCommand::Remove => "remove",
+            HelperCommand::Save => "save",
+            HelperCommand::Stream => "stream",
+            HelperCommand::Order => "order",
+            HelperCommand::Shell => "shell",
+            HelperCommand::Reload => "reload",
+            HelperCommand::History => "history",
+            HelperCommand::Help => "help",
+            HelperCommand::Exit => "exit",
+        }
+    }
 }
 
 pub trait Pinable {
@@ -43,7 +80,10 @@ mod tests {
 
     #[test]
     fn test_to_enum_returns_good_part() {
-        let scenarios = vec!["pin", "unpin", "ban", "unban", "list", "help", "exit"];
+        let scenarios = vec![
+            "pin", "unpin", "ban", "unban", "list", "add", "remove", "save", "stream", "order",
+            "shell", "reload", "history", "help", "exit",
+        ];
 
         for scenario in scenarios {
             assert!(HelperCommand::to_enum(scenario).is_some())
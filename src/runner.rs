@@ -1,27 +1,85 @@
-use std::path::PathBuf;
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, PipeReader, Read};
+use std::path::{Path, PathBuf};
 use std::process::{Child, Command, Stdio};
 use std::sync::mpsc;
 use std::thread;
-use std::{collections::HashSet, sync::Arc};
+use std::time::SystemTime;
+use std::{
+    collections::{BTreeMap, HashSet},
+    sync::Arc,
+};
 
-#[derive(Debug)]
-pub struct Output {
-    terminal_name: String,
-    output: Vec<u8>,
-    command: String,
-}
 use crate::{
-    command_option::CommandOption,
+    command_option::{CommandLine, CommandOption, Pipeline, Redirect},
     help::Help,
+    history,
+    interrupt::Children,
+    lexer::Variables,
+    output::{self, Output, StageOutput, Status, Summary, Tag},
     ports::{Banable, HelperCommand, Pinable},
-    terminal::{self, Terminal},
+    selector::{self, Groups, Selector},
+    terminal::{self, Config, ConfigError, ConfigSource, Shell, Terminal},
 };
 
+/// How the output of the commands is presented
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputMode {
+    /// The whole output of a terminal is printed once its command line finishes
+    #[default]
+    Buffered,
+    /// Every line is printed as soon as it arrives, prefixed by the terminal name
+    Streamed,
+}
+
+/// The order in which the terminals output is printed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputOrder {
+    /// The first terminal to finish is the first printed
+    #[default]
+    Completion,
+    /// Terminals are printed in the order they appear in the config file,
+    /// early finishers are held back until the ones before them are done
+    Config,
+}
+
+static DEFAULT_PROMPT: &str = "[{selection}]> ";
+
+/// How many pinned or banned names the prompt shows before counting the rest
+const PROMPT_NAMES: usize = 3;
+
+/// How a line ended, the batch mode exits with a failure when a line failed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineStatus {
+    Done,
+    /// The line could not run or failed in at least one terminal
+    Failed,
+    /// The `exit` helper was called
+    Exit,
+}
+
+impl From<Summary> for LineStatus {
+    fn from(summary: Summary) -> Self {
+        match summary.failed.is_empty() {
+            true => LineStatus::Done,
+            false => LineStatus::Failed,
+        }
+    }
+}
+
 /// Main logic of the application of handling terminals is done here
+#[derive(Default)]
 pub struct Runner {
     /// All terminals loaded from config file
     pub all_terminals: Vec<Terminal>,
 
+    /// Environment variables exported in every terminal
+    pub env: BTreeMap<String, String>,
+
+    /// Named lists of terminals selected with `@group`
+    pub groups: Groups,
+
     /// the set of pinned terminals has the highest priority
     /// if this one is empty it will look into excluded set
     pub pinned_terminals: HashSet<Terminal>,
@@ -29,30 +87,271 @@ pub struct Runner {
     /// the set of excluded terminals
     /// all the terminals in excluded set will be removed
     pub banned_terminals: HashSet<Terminal>,
+
+    pub output_mode: OutputMode,
+
+    pub output_order: OutputOrder,
+
+    pub shell: Shell,
+
+    /// Where the config was loaded from
+    pub source: ConfigSource,
+
+    /// Reload the config when the file changes
+    pub watch: bool,
+
+    /// The commands read nothing, set when the lines themselves are read from stdin
+    pub detach_stdin: bool,
+
+    /// Modification time of the config file at the last load
+    modified: Option<SystemTime>,
+
+    /// The lines entered so far, recalled with `!!` and `!n`
+    pub history: Vec<String>,
+
+    /// Processes of the running line, interrupted by Ctrl-C
+    pub children: Children,
+
+    /// Template of the prompt from the config
+    pub prompt: Option<String>,
+
+    /// The config file as written, saved back with the terminal changes only
+    written: Config,
+
+    /// Names of the terminals added since the config was loaded
+    added: Vec<String>,
 }
 
 impl Runner {
-    pub fn new(path: PathBuf) -> Self {
-        let terminals = terminal::Config::load(path);
+    pub fn new(source: ConfigSource) -> Result<Self, ConfigError> {
+        let config = source.load()?;
+        let written = source.read_as_written()?;
 
-        Runner {
-            all_terminals: terminals,
+        Ok(Runner {
+            all_terminals: config.terminals,
+            env: config.env,
+            groups: config.groups,
             pinned_terminals: HashSet::new(),
             banned_terminals: HashSet::new(),
+            output_mode: OutputMode::default(),
+            output_order: OutputOrder::default(),
+            shell: config.shell,
+            prompt: config.prompt,
+            modified: source.modified(),
+            source,
+            watch: false,
+            detach_stdin: false,
+            history: vec![],
+            children: Children::default(),
+            written,
+            added: vec![],
+        })
+    }
+
+    /// Load the config again, the pinned and banned terminals are kept by name
+    /// and the shell stays on or off as toggled
+    pub fn reload(&mut self) {
+        let loaded = self
+            .source
+            .load()
+            .and_then(|config| Ok((config, self.source.read_as_written()?)));
+        let (config, written) = match loaded {
+            Ok(configs) => configs,
+            Err(e) => return println!("Could not reload the config: {}", e),
+        };
+        self.modified = self.source.modified();
+        self.written = written;
+        self.added.clear();
+
+        let added = config
+            .terminals
+            .iter()
+            .filter(|t| !self.all_terminals.iter().any(|old| old.name == t.name))
+            .count();
+        let removed = self
+            .all_terminals
+            .iter()
+            .filter(|old| !config.terminals.iter().any(|t| t.name == old.name))
+            .count();
+
+        self.pinned_terminals = reconcile(&self.pinned_terminals, &config.terminals, "pinned");
+        self.banned_terminals = reconcile(&self.banned_terminals, &config.terminals, "banned");
+        self.all_terminals = config.terminals;
+        self.env = config.env;
+        self.groups = config.groups;
+        self.prompt = config.prompt;
+        self.shell = Shell {
+            enabled: self.shell.enabled,
+            ..config.shell
+        };
+
+        println!(
+            "reloaded: {} terminals ({} added, {} removed)",
+            self.all_terminals.len(),
+            added,
+            removed
+        );
+    }
+
+    /// Add a terminal to the loaded ones, the path is relative to the current directory
+    pub fn add_terminal(&mut self, name: &str, path: &str) {
+        let base = std::env::current_dir().unwrap_or_default();
+        let path = terminal::resolve_path(path, &base);
+
+        if self.all_terminals.iter().any(|t| t.name == name) {
+            return println!("terminal `{}` already exists", name);
+        }
+        if !terminal::is_selectable(name) {
+            return println!("terminal name `{}` can not be used with pin and ban", name);
+        }
+        if !Path::new(&path).is_dir() {
+            return println!("{} is not a directory", path);
+        }
+
+        println!("added: {} ({})", name, path);
+        self.added.push(name.to_string());
+        self.all_terminals.push(Terminal {
+            name: name.to_string(),
+            path,
+            ..Default::default()
+        });
+    }
+
+    /// Remove the terminals with the given names, they are unpinned and unbanned too
+    pub fn remove_terminals(&mut self, names: Vec<String>) {
+        for name in names {
+            match self.all_terminals.iter().position(|t| t.name == name) {
+                Some(index) => {
+                    let terminal = self.all_terminals.remove(index);
+                    self.pinned_terminals.remove(&terminal);
+                    self.banned_terminals.remove(&terminal);
+                    println!("removed: {}", name);
+                }
+                None => println!("warning: `{}` did not match any terminal", name),
+            }
         }
     }
 
+    /// Write the settings and the current terminals as yaml to the file, the loaded config
+    /// by default where only the added and removed terminals change what is written
+    pub fn save(&mut self, file: Option<&String>) {
+        let (path, config) = match file {
+            Some(file) => (
+                PathBuf::from(file),
+                Config {
+                    env: self.env.clone(),
+                    shell: self.shell.clone(),
+                    groups: self.groups.clone(),
+                    discover: None,
+                    prompt: self.prompt.clone(),
+                    terminals: self.all_terminals.clone(),
+                },
+            ),
+            None => (self.source.path.clone(), self.written_config()),
+        };
+
+        match config.save(&path) {
+            Ok(()) => println!(
+                "saved {} terminals to {}",
+                config.terminals.len(),
+                path.display()
+            ),
+            Err(e) => println!("Could not save the config: {}", e),
+        }
+
+        // saving is not a change to reload when watching
+        if path == self.source.path {
+            self.modified = self.source.modified();
+        }
+    }
+
+    /// The config file as it was written, without the removed terminals and with the added ones.
+    /// The discovered terminals stay out of it, they are found again on the next load
+    fn written_config(&self) -> Config {
+        let mut config = self.written.clone();
+        let is_added = |name: &String| self.added.contains(name);
+
+        config.terminals.retain(|t| {
+            !is_added(&t.name) && self.all_terminals.iter().any(|old| old.name == t.name)
+        });
+        config.terminals.extend(
+            self.all_terminals
+                .iter()
+                .filter(|t| is_added(&t.name))
+                .cloned(),
+        );
+
+        config
+    }
+
+    /// Reload when watching and the config file changed since it was loaded,
+    /// true when the reload dropped every pinned terminal
+    fn reload_if_changed(&mut self) -> bool {
+        if !self.watch || self.source.modified() == self.modified {
+            return false;
+        }
+
+        let pinned = !self.pinned_terminals.is_empty();
+        self.reload();
+
+        pinned && self.pinned_terminals.is_empty()
+    }
+
+    /// The prompt showing which terminals the next line runs in, the template
+    /// placeholders are `{selection}`, `{active}`, `{total}`, `{pinned}` and `{banned}`
+    pub fn prompt(&self) -> String {
+        let names = |set: &HashSet<Terminal>| {
+            let names = self
+                .all_terminals
+                .iter()
+                .filter(|terminal| set.contains(terminal))
+                .map(|terminal| terminal.name.as_str())
+                .collect::<Vec<_>>();
+
+            // a long list would push the command out of the screen
+            match names.len() {
+                0..=PROMPT_NAMES => names.join(","),
+                len => format!(
+                    "{},+{}",
+                    names[..PROMPT_NAMES].join(","),
+                    len - PROMPT_NAMES
+                ),
+            }
+        };
+        let active = self.active_terminals().len();
+        let total = self.all_terminals.len();
+
+        let selection = if !self.pinned_terminals.is_empty() {
+            format!("pinned: {}", names(&self.pinned_terminals))
+        } else if !self.banned_terminals.is_empty() {
+            format!("{}/{} active", active, total)
+        } else {
+            format!("all {}", total)
+        };
+
+        self.prompt
+            .as_deref()
+            .unwrap_or(DEFAULT_PROMPT)
+            .replace("{selection}", &selection)
+            .replace("{active}", &active.to_string())
+            .replace("{total}", &total.to_string())
+            .replace("{pinned}", &names(&self.pinned_terminals))
+            .replace("{banned}", &names(&self.banned_terminals))
+    }
+
     pub fn active_terminals(&self) -> Vec<Terminal> {
         // if we have pinned terminals than return the pinned terminals
-        if self.pinned_terminals.len() > 0 {
+        // keeping the config order rather than the hashset one
+        if !self.pinned_terminals.is_empty() {
             return self
-                .pinned_terminals
+                .all_terminals
                 .clone()
                 .into_iter()
+                .filter(|terminal| self.pinned_terminals.contains(terminal))
                 .collect::<Vec<Terminal>>();
         }
 
-        if self.banned_terminals.len() > 0 {
+        if !self.banned_terminals.is_empty() {
             return self
                 .all_terminals
                 .clone()
@@ -64,102 +363,186 @@ impl Runner {
         self.all_terminals.clone()
     }
 
-    pub fn execute_cmds(&self, commands: Vec<CommandOption>) {
+    /// Expand the `!!` and `!n` history references and record the line in the history,
+    /// the expanded line is echoed like in bash
+    pub fn recall(&mut self, line: &str) -> Option<String> {
+        let expanded = match history::expand(line.trim(), &self.history) {
+            Ok(expanded) => expanded,
+            Err(e) => {
+                println!("{}", e);
+                return None;
+            }
+        };
+
+        if expanded != line.trim() {
+            println!("{}", expanded);
+        }
+        // consecutive duplicates are skipped like the line editor does
+        if !expanded.is_empty() && self.history.last() != Some(&expanded) {
+            self.history.push(expanded.clone());
+        }
+
+        Some(expanded)
+    }
+
+    /// Dispatch an input line to the helper commands, the shell or the pipeline executor,
+    /// a leading `@PJ1,PJ3` runs the line in the selected terminals only
+    pub fn execute_line(&mut self, input: String) -> LineStatus {
+        let pins_dropped = self.reload_if_changed();
+
+        if input.trim().is_empty() {
+            return LineStatus::Done;
+        }
+
+        let (target, input) = split_target(&input);
+        let first_word = input.split_whitespace().next().unwrap_or_default();
+        let is_helper = HelperCommand::to_enum(first_word).is_some();
+
+        if target.is_some() && is_helper {
+            println!("Helper commands can not be targeted, use pin and ban instead");
+            return LineStatus::Failed;
+        }
+
+        let terminals = match target {
+            // `select` takes no selectors as all negated, that is every terminal
+            Some(selectors) if selectors.is_empty() => {
+                println!("Missing target after @");
+                return LineStatus::Failed;
+            }
+            Some(selectors) => match self.select(selectors) {
+                terminals if terminals.is_empty() => return LineStatus::Failed,
+                terminals => terminals,
+            },
+            // the line was meant for the pinned terminals only, not for all of them
+            None if pins_dropped && !is_helper => {
+                println!("The pinned terminals are no longer in the config, the line was not run");
+                return LineStatus::Failed;
+            }
+            None => self.active_terminals(),
+        };
+
+        if self.shell.enabled && !is_helper {
+            return self.execute_shell(terminals, input.to_string()).into();
+        }
+
+        let command_line = match CommandOption::from_input(input.to_string()) {
+            Ok(command_line) => command_line,
+            Err(e) => {
+                println!("Could not parse the command: {}", e);
+                return LineStatus::Failed;
+            }
+        };
+
+        if is_helper {
+            self.execute_helper_cmd(command_line.first_command().clone())
+        } else {
+            self.execute_cmds(terminals, command_line).into()
+        }
+    }
+
+    /// Run the raw line with `<shell> -c` in every terminal
+    pub fn execute_shell(&self, terminals: Vec<Terminal>, input: String) -> Summary {
+        let command = CommandOption {
+            name: self.shell.program.clone(),
+            args: vec!["-c".to_string(), input.trim().to_string()],
+            ..Default::default()
+        };
+
+        self.execute_cmds(
+            terminals,
+            CommandLine::from(Pipeline {
+                commands: vec![command],
+            }),
+        )
+    }
+
+    /// Run the command line in every terminal and print the summary of the statuses
+    pub fn execute_cmds(&self, terminals: Vec<Terminal>, command_line: CommandLine) -> Summary {
         let (tx, rx) = mpsc::channel();
-        let arc_cmds = Arc::new(commands);
-        let terminals = self.active_terminals();
+        let arc_cmds = Arc::new(command_line);
         let active_terminal_count = terminals.len();
 
-        for terminal in terminals {
+        let colored = output::use_colors();
+        self.children.reset();
+
+        for (index, terminal) in terminals.into_iter().enumerate() {
             let txc = tx.clone();
             let cmds = Arc::clone(&arc_cmds);
+            let children = self.children.clone();
+            let detach_stdin = self.detach_stdin;
+            let position = self.all_terminals.iter().position(|t| *t == terminal);
+            let variables = terminal.variables(position.unwrap_or(index), &self.env);
+            let tag = match self.output_mode {
+                OutputMode::Buffered => None,
+                OutputMode::Streamed => Some(Tag::new(&terminal.name, index, colored)),
+            };
 
             thread::spawn(move || {
-                let commands = cmds.clone();
-
-                let mut prev_command = None;
-                let mut errors = vec![];
-
-                for command in commands.iter() {
-                    let stdin = prev_command.map_or(Stdio::inherit(), |output: Child| {
-                        if output.stdout.is_some() {
-                            Stdio::from(output.stdout.unwrap())
-                        } else {
-                            Stdio::inherit()
-                        }
-                    });
+                let output =
+                    run_command_line(&terminal, &cmds, &variables, tag, &children, detach_stdin);
+                txc.send((index, output)).unwrap();
+            });
+        }
 
-                    let output = Command::new(command.name.clone())
-                        .args(command.args.clone())
-                        .stdin(stdin)
-                        .stdout(Stdio::piped())
-                        .current_dir(terminal.path.clone())
-                        .spawn();
-
-                    match output {
-                        Ok(output) => {
-                            prev_command = Some(output);
-                        }
-                        Err(e) => {
-                            prev_command = None;
-                            errors.push(e.to_string().clone());
-                        }
-                    }
-                }
+        let mut summary = Summary::default();
+        let mut report = |received: Output| {
+            if self.output_mode == OutputMode::Buffered {
+                println!("{}", received.render(colored));
+            }
+            summary.add(&received);
+        };
 
-                let output = if let Some(final_command) = prev_command {
-                    final_command.wait_with_output().unwrap().stdout
-                } else {
-                    errors.join(",").as_bytes().to_owned()
-                };
+        // outputs that finished before their turn in config order
+        let mut pending: Vec<Option<Output>> = (0..active_terminal_count).map(|_| None).collect();
+        let mut next = 0;
 
-                txc.send(Output {
-                    terminal_name: terminal.name.clone(),
-                    output: output.clone(),
-                    command: commands
-                        .iter()
-                        .map(|c| c.clone().to_string())
-                        .collect::<Vec<_>>()
-                        .join(" | "),
-                })
-                .unwrap();
-            });
+        for _ in 0..active_terminal_count {
+            let (index, received) = rx.recv().unwrap();
+
+            match self.output_order {
+                OutputOrder::Completion => report(received),
+                OutputOrder::Config => {
+                    pending[index] = Some(received);
+
+                    while let Some(ready) = pending.get_mut(next).and_then(|slot| slot.take()) {
+                        report(ready);
+                        next += 1;
+                    }
+                }
+            }
         }
 
-        for _ in 0..active_terminal_count {
-            let received = rx.recv().unwrap();
-            println!(
-                "[{}]> {}\n{}",
-                received.terminal_name,
-                received.command,
-                std::str::from_utf8(&received.output).unwrap()
-            );
+        if self.children.is_interrupted() {
+            println!("interrupted");
         }
+        println!("{}", summary);
+
+        summary
     }
 
-    pub fn execute_helper_cmd(&mut self, command: CommandOption) {
+    pub fn execute_helper_cmd(&mut self, command: CommandOption) -> LineStatus {
         match HelperCommand::to_enum(&command.name).unwrap() {
             HelperCommand::Help => println!("{}", Help::display()),
             HelperCommand::Ban => {
-                if command.args.len() > 0 {
+                if !command.args.is_empty() {
                     self.ban(command.args);
                 }
             }
             HelperCommand::Unban => {
-                if command.args.len() > 0 {
+                if !command.args.is_empty() {
                     self.unban(command.args);
                 } else {
                     self.banned_terminals = HashSet::new();
                 }
             }
             HelperCommand::Pin => {
-                if command.args.len() > 0 {
+                if !command.args.is_empty() {
                     self.pin(command.args);
                 }
             }
             HelperCommand::Unpin => {
-                if command.args.len() > 0 {
-                    self.unban(command.args);
+                if !command.args.is_empty() {
+                    self.unpin(command.args);
                 } else {
                     self.pinned_terminals = HashSet::new();
                 }
@@ -169,41 +552,150 @@ impl Runner {
                     println!("{}", terminal.name);
                 }
             }
-            HelperCommand::Exit => std::process::exit(0),
+            HelperCommand::Add => match command.args.as_slice() {
+                [name, path] => self.add_terminal(name, path),
+                _ => println!("Usage: add <name> <path>"),
+            },
+            HelperCommand::Remove => self.remove_terminals(command.args),
+            HelperCommand::Save => self.save(command.args.first()),
+            HelperCommand::Stream => {
+                self.output_mode = match command.args.first().map(|arg| arg.as_str()) {
+                    Some("on") => OutputMode::Streamed,
+                    Some("off") => OutputMode::Buffered,
+                    _ if self.output_mode == OutputMode::Streamed => OutputMode::Buffered,
+                    _ => OutputMode::Streamed,
+                };
+                println!("output mode: {:?}", self.output_mode);
+            }
+            HelperCommand::Order => {
+                self.output_order = match command.args.first().map(|arg| arg.as_str()) {
+                    Some("config") => OutputOrder::Config,
+                    Some("completion") => OutputOrder::Completion,
+                    _ if self.output_order == OutputOrder::Config => OutputOrder::Completion,
+                    _ => OutputOrder::Config,
+                };
+                println!("output order: {:?}", self.output_order);
+            }
+            HelperCommand::Shell => {
+                self.shell.enabled = match command.args.first().map(|arg| arg.as_str()) {
+                    Some("on") => true,
+                    Some("off") => false,
+                    _ => !self.shell.enabled,
+                };
+                match self.shell.enabled {
+                    true => println!("shell mode: {} -c", self.shell.program),
+                    false => println!("shell mode: off"),
+                }
+            }
+            HelperCommand::Reload => self.reload(),
+            HelperCommand::History => {
+                for (index, line) in self.history.iter().enumerate() {
+                    println!("{:>5}  {}", index + 1, line);
+                }
+            }
+            HelperCommand::Exit => return LineStatus::Exit,
         };
+
+        LineStatus::Done
     }
 
-    fn add_to_set(&mut self, set_type: SetType, names: Vec<String>) {
+    /// Resolve the selectors of a helper command, reporting the ones matching nothing
+    fn select(&self, selectors: Vec<String>) -> Vec<Terminal> {
+        let selectors = match selectors
+            .iter()
+            .map(|selector| Selector::parse(selector))
+            .collect::<Result<Vec<_>, _>>()
+        {
+            Ok(selectors) => selectors,
+            Err(e) => {
+                println!("{}", e);
+                return vec![];
+            }
+        };
+
+        let selection = selector::resolve(&selectors, &self.all_terminals, &self.groups);
+        for selector in selection.unmatched {
+            println!("warning: `{}` did not match any terminal", selector);
+        }
+
+        selection.terminals.into_iter().cloned().collect()
+    }
+
+    fn add_to_set(&mut self, set_type: SetType, selectors: Vec<String>) {
+        let terminals = self.select(selectors);
+        report(&set_type.to_string(), &terminals);
+
         // pretty sure ther should be a more elegant way to do it
         let set = match set_type {
             SetType::Banned => &mut self.banned_terminals,
             SetType::Pinned => &mut self.pinned_terminals,
         };
 
-        for name in names {
-            let terminal = self.all_terminals.iter().find(|term| term.name == name);
-
-            if terminal.is_some() {
-                set.insert(terminal.unwrap().clone());
-            }
-        }
+        set.extend(terminals);
     }
 
-    fn remove_from_set(&mut self, set_type: SetType, names: Vec<String>) {
+    fn remove_from_set(&mut self, set_type: SetType, selectors: Vec<String>) {
+        let terminals = self.select(selectors);
+        report(&format!("un{}", set_type), &terminals);
+
         let set = match set_type {
             SetType::Banned => &mut self.banned_terminals,
             SetType::Pinned => &mut self.pinned_terminals,
         };
 
-        for name in names {
-            // try to find the terminal in hashset
-            let maybe_terminal = set.iter().find(|term| term.name == name);
+        for terminal in terminals.iter() {
+            set.remove(terminal);
+        }
+    }
+}
 
-            if maybe_terminal.is_some() {
-                let terminal = maybe_terminal.unwrap().clone();
-                set.remove(&terminal);
-            }
+/// The terminals of the reloaded config whose names were in the set,
+/// the names which disappeared from the config are reported
+fn reconcile(set: &HashSet<Terminal>, terminals: &[Terminal], action: &str) -> HashSet<Terminal> {
+    for old in set.iter() {
+        if !terminals.iter().any(|t| t.name == old.name) {
+            println!(
+                "warning: {} terminal `{}` is no longer in the config",
+                action, old.name
+            );
+        }
+    }
+
+    terminals
+        .iter()
+        .filter(|t| set.iter().any(|old| old.name == t.name))
+        .cloned()
+        .collect()
+}
+
+/// Split the `@PJ1,PJ3` target prefix from the rest of the line
+fn split_target(input: &str) -> (Option<Vec<String>>, &str) {
+    let trimmed = input.trim_start();
+
+    match trimmed.strip_prefix('@') {
+        Some(rest) => {
+            let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+            let selectors = rest[..end]
+                .split(',')
+                .filter(|selector| !selector.is_empty())
+                .map(|selector| selector.to_string())
+                .collect();
+
+            (Some(selectors), &rest[end..])
         }
+        None => (None, input),
+    }
+}
+
+/// Print which terminals a helper command applied to
+fn report(action: &str, terminals: &[Terminal]) {
+    if !terminals.is_empty() {
+        let names = terminals
+            .iter()
+            .map(|terminal| terminal.name.as_str())
+            .collect::<Vec<_>>();
+
+        println!("{}: {}", action, names.join(", "));
     }
 }
 
@@ -232,28 +724,316 @@ enum SetType {
     Pinned,
 }
 
+impl fmt::Display for SetType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SetType::Banned => write!(f, "banned"),
+            SetType::Pinned => write!(f, "pinned"),
+        }
+    }
+}
+
+/// Run the pipelines of the line one after the other in the terminal directory,
+/// `&&` and `||` short circuit on the status of the previous pipeline
+fn run_command_line(
+    terminal: &Terminal,
+    command_line: &CommandLine,
+    variables: &Variables,
+    tag: Option<Tag>,
+    children: &Children,
+    detach_stdin: bool,
+) -> Output {
+    let mut output = Output {
+        terminal_name: terminal.name.clone(),
+        command: command_line.to_string(),
+        stdout: vec![],
+        stages: vec![],
+        status: Status::Exited(0),
+    };
+
+    let command_line = match command_line.expand(variables) {
+        Ok(command_line) => command_line,
+        Err(e) => {
+            output.stages.push(StageOutput {
+                name: "sila".to_string(),
+                stderr: format!("{}\n", e).into_bytes(),
+                status: Status::NotStarted,
+            });
+            output.status = Status::NotStarted;
+
+            return output;
+        }
+    };
+    output.command = command_line.to_string();
+
+    output.status = run_pipeline(
+        terminal,
+        &command_line.first,
+        variables,
+        &tag,
+        children,
+        detach_stdin,
+        &mut output,
+    );
+
+    for (connector, pipeline) in command_line.rest.iter() {
+        // Ctrl-C stops the whole line, not only the running pipeline
+        if children.is_interrupted() {
+            break;
+        }
+
+        if connector.should_run(output.status.success()) {
+            output.status = run_pipeline(
+                terminal,
+                pipeline,
+                variables,
+                &tag,
+                children,
+                detach_stdin,
+                &mut output,
+            );
+        }
+    }
+
+    output
+}
+
+/// Spawn every stage of the pipeline, chaining stdout into the next stage stdin
+/// and capturing each stage stderr and exit status into the output,
+/// when a tag is provided the lines are also printed as soon as they are read
+fn run_pipeline(
+    terminal: &Terminal,
+    pipeline: &Pipeline,
+    variables: &Variables,
+    tag: &Option<Tag>,
+    children: &Children,
+    detach_stdin: bool,
+    output: &mut Output,
+) -> Status {
+    let mut prev_stdout: Option<PipeReader> = None;
+    let mut spawned = vec![];
+
+    for (index, command) in pipeline.commands.iter().enumerate() {
+        // only the first stage reads from the terminal, unless the lines come from stdin,
+        // a stage after one that could not be spawned reads nothing
+        let stdin = match prev_stdout.take() {
+            Some(stdout) => Stdio::from(stdout),
+            None if index == 0 && !detach_stdin => Stdio::inherit(),
+            None => Stdio::null(),
+        };
+
+        match spawn_stage(terminal, command, variables, stdin) {
+            Ok((mut child, stdout)) => {
+                children.add(child.id());
+                prev_stdout = Some(stdout);
+                // stderr is not captured when it is redirected
+                let stderr = child.stderr.take().map(|stderr| {
+                    let echo = tag.clone().map(|tag| {
+                        let stage = command.name.clone();
+                        move |line: &str| println!("{}", tag.stderr_line(&stage, line))
+                    });
+                    read_in_thread(stderr, echo)
+                });
+                spawned.push((command.name.clone(), Ok((child, stderr))));
+            }
+            Err(e) => {
+                // keep the spawn error attributed to the stage that caused it
+                if let Some(tag) = &tag {
+                    println!("{}", tag.stderr_line(&command.name, &e));
+                }
+                spawned.push((command.name.clone(), Err(e)));
+            }
+        }
+    }
+
+    if let Some(final_stdout) = prev_stdout {
+        let echo = tag
+            .as_ref()
+            .map(|tag| move |line: &str| println!("{}", tag.stdout_line(line)));
+        output.stdout.extend(drain(final_stdout, echo));
+    }
+
+    // every stage is waited, not only the last one, so none is left as a zombie
+    let stages: Vec<StageOutput> = spawned
+        .into_iter()
+        .map(|(name, stage)| match stage {
+            Ok((mut child, stderr)) => {
+                let status = child.wait().map_or(Status::NotStarted, Status::from);
+                children.remove(child.id());
+
+                StageOutput {
+                    name,
+                    stderr: stderr
+                        .map(|stderr| stderr.join().unwrap_or_default())
+                        .unwrap_or_default(),
+                    status,
+                }
+            }
+            Err(error) => StageOutput {
+                name,
+                stderr: format!("{}\n", error).into_bytes(),
+                status: Status::NotStarted,
+            },
+        })
+        .collect();
+
+    let status = Status::of_pipeline(&stages);
+    output.stages.extend(stages);
+
+    status
+}
+
+/// Where a standard stream of a stage is wired
+enum Target {
+    /// Into the pipe read by the next stage or captured for the last one
+    Pipe,
+    /// Into the pipe captured by sila, the default for stderr
+    Capture,
+    File(File),
+}
+
+impl Target {
+    fn try_clone(&self) -> io::Result<Target> {
+        Ok(match self {
+            Target::Pipe => Target::Pipe,
+            Target::Capture => Target::Capture,
+            Target::File(file) => Target::File(file.try_clone()?),
+        })
+    }
+}
+
+/// Spawn a single stage in the terminal directory and environment applying its redirections
+/// in order, returns the child along with the pipe its stdout is written to
+fn spawn_stage(
+    terminal: &Terminal,
+    command: &CommandOption,
+    variables: &Variables,
+    stdin: Stdio,
+) -> Result<(Child, PipeReader), String> {
+    let directory = Path::new(&terminal.path);
+    let mut stdin = stdin;
+    let mut stdout = Target::Pipe;
+    let mut stderr = Target::Capture;
+
+    let open = |path: &String, options: &OpenOptions| {
+        options
+            .open(directory.join(path))
+            .map_err(|e| format!("{}: {}", path, e))
+    };
+    let output_options = |append: bool| {
+        let mut options = OpenOptions::new();
+        options
+            .create(true)
+            .write(true)
+            .append(append)
+            .truncate(!append);
+        options
+    };
+
+    for redirect in command.redirects.iter() {
+        match redirect {
+            Redirect::Stdin(path) => {
+                stdin = Stdio::from(open(path, OpenOptions::new().read(true))?);
+            }
+            Redirect::Stdout { path, append } => {
+                stdout = Target::File(open(path, &output_options(*append))?);
+            }
+            Redirect::Stderr { path, append } => {
+                stderr = Target::File(open(path, &output_options(*append))?);
+            }
+            Redirect::StderrToStdout => {
+                stderr = stdout.try_clone().map_err(|e| e.to_string())?;
+            }
+        }
+    }
+
+    let (reader, writer) = io::pipe().map_err(|e| e.to_string())?;
+    let to_stdio = |target: Target| -> io::Result<Stdio> {
+        Ok(match target {
+            Target::Pipe => Stdio::from(writer.try_clone()?),
+            Target::Capture => Stdio::piped(),
+            Target::File(file) => Stdio::from(file),
+        })
+    };
+
+    // the command is dropped once spawned so that the pipe
+    // is only held open by the child
+    let child = Command::new(&command.name)
+        .args(&command.args)
+        .stdin(stdin)
+        .stdout(to_stdio(stdout).map_err(|e| e.to_string())?)
+        .stderr(to_stdio(stderr).map_err(|e| e.to_string())?)
+        .current_dir(&terminal.path)
+        .envs(&variables.env)
+        .spawn()
+        .map_err(|e| e.to_string())?;
+
+    Ok((child, reader))
+}
+
+/// Drain a reader on a separate thread so that a full pipe never blocks the child
+fn read_in_thread<R, F>(reader: R, echo: Option<F>) -> thread::JoinHandle<Vec<u8>>
+where
+    R: Read + Send + 'static,
+    F: Fn(&str) + Send + 'static,
+{
+    thread::spawn(move || drain(reader, echo))
+}
+
+/// Read everything line by line, handing every line to `echo` as soon as it arrives
+fn drain<R: Read, F: Fn(&str)>(reader: R, echo: Option<F>) -> Vec<u8> {
+    let mut reader = BufReader::new(reader);
+    let mut buffer = vec![];
+
+    loop {
+        let start = buffer.len();
+        match reader.read_until(b'\n', &mut buffer) {
+            Ok(0) | Err(_) => break,
+            Ok(_) => {
+                if let Some(echo) = &echo {
+                    let line = String::from_utf8_lossy(&buffer[start..]);
+                    echo(line.trim_end_matches(&['\r', '\n'][..]));
+                }
+            }
+        }
+    }
+
+    buffer
+}
+
 #[cfg(test)]
 mod tests {
     use crate::{
+        command_option::CommandOption,
+        interrupt::Children,
+        lexer::Variables,
+        output::Status,
         ports::{Banable, Pinable},
-        runner::Runner,
-        terminal::Terminal,
+        runner::{drain, run_command_line, split_target, LineStatus, Runner},
+        terminal::{Config, ConfigSource, Terminal},
+        test_utils::TempDir,
+    };
+    use std::{
+        cell::RefCell,
+        collections::{BTreeMap, HashSet},
     };
-    use std::collections::HashSet;
 
     fn get_terminals() -> Vec<Terminal> {
         vec![
             Terminal {
                 name: "T1".to_string(),
                 path: "/path/t1".to_string(),
+                ..Default::default()
             },
             Terminal {
                 name: "T2".to_string(),
                 path: "/path/t2".to_string(),
+                ..Default::default()
             },
             Terminal {
                 name: "T3".to_string(),
                 path: "/path/t3".to_string(),
+                ..Default::default()
             },
         ]
     }
@@ -265,6 +1045,7 @@ mod tests {
             Terminal {
                 name: str.to_string(),
                 path: format!("/path/{}", str.to_lowercase()).to_string(),
+                ..Default::default()
             }
         }
     }
@@ -280,6 +1061,7 @@ mod tests {
             all_terminals: get_terminals().clone(),
             pinned_terminals: to_hashset(terminals_to_pin.clone()),
             banned_terminals: HashSet::new(),
+            ..Default::default()
         };
 
         assert_eq!(sila.active_terminals(), terminals_to_pin);
@@ -293,6 +1075,7 @@ mod tests {
             all_terminals: get_terminals().clone(),
             pinned_terminals: HashSet::new(),
             banned_terminals,
+            ..Default::default()
         };
 
         assert_eq!(
@@ -308,6 +1091,7 @@ mod tests {
             all_terminals: get_terminals().clone(),
             pinned_terminals: HashSet::new(),
             banned_terminals: HashSet::new(),
+            ..Default::default()
         };
 
         sila.pin(vec!["T4".to_string()]);
@@ -320,6 +1104,7 @@ mod tests {
             all_terminals: get_terminals().clone(),
             pinned_terminals: HashSet::new(),
             banned_terminals: HashSet::new(),
+            ..Default::default()
         };
 
         let terminal = Terminal::new("T3");
@@ -337,6 +1122,7 @@ mod tests {
             all_terminals: get_terminals().clone(),
             pinned_terminals: HashSet::new(),
             banned_terminals: HashSet::new(),
+            ..Default::default()
         };
         sila.unpin(vec!["T3".to_string()]);
 
@@ -349,6 +1135,7 @@ mod tests {
             all_terminals: get_terminals().clone(),
             pinned_terminals: to_hashset(get_terminals().clone()),
             banned_terminals: HashSet::new(),
+            ..Default::default()
         };
 
         sila.unpin(vec!["T3".to_string()]);
@@ -360,12 +1147,32 @@ mod tests {
         assert_eq!(sila.pinned_terminals, expected_set);
     }
 
+    #[test]
+    fn test_unpin_command_keeps_banned_terminals() {
+        let mut sila = Runner {
+            all_terminals: get_terminals().clone(),
+            pinned_terminals: to_hashset(vec![Terminal::new("T1")]),
+            banned_terminals: to_hashset(vec![Terminal::new("T3")]),
+            ..Default::default()
+        };
+
+        sila.execute_helper_cmd(CommandOption {
+            name: "unpin".to_string(),
+            args: vec!["T1".to_string(), "T3".to_string()],
+            ..Default::default()
+        });
+
+        assert_eq!(sila.pinned_terminals, HashSet::new());
+        assert_eq!(sila.banned_terminals, to_hashset(vec![Terminal::new("T3")]));
+    }
+
     #[test]
     fn test_ban_one_terminal() {
         let mut sila = Runner {
             all_terminals: get_terminals().clone(),
             pinned_terminals: HashSet::new(),
             banned_terminals: HashSet::new(),
+            ..Default::default()
         };
 
         sila.ban(vec!["T3".to_string()]);
@@ -386,6 +1193,7 @@ mod tests {
             all_terminals: get_terminals().clone(),
             pinned_terminals: HashSet::new(),
             banned_terminals: HashSet::new(),
+            ..Default::default()
         };
 
         sila.ban(vec!["T3".to_string()]);
@@ -393,4 +1201,415 @@ mod tests {
 
         assert_eq!(sila.active_terminals(), get_terminals().clone());
     }
+
+    #[test]
+    fn test_drain_echoes_every_line() {
+        let lines = RefCell::new(vec![]);
+        let input = "first\r\nsecond\nlast".as_bytes();
+
+        let output = drain(
+            input,
+            Some(|line: &str| lines.borrow_mut().push(line.to_string())),
+        );
+
+        assert_eq!(output, input);
+        assert_eq!(lines.into_inner(), vec!["first", "second", "last"]);
+    }
+
+    #[test]
+    fn test_pinned_terminals_keep_config_order() {
+        let mut sila = Runner {
+            all_terminals: get_terminals().clone(),
+            ..Default::default()
+        };
+
+        sila.pin(vec!["T3".to_string(), "T1".to_string(), "T2".to_string()]);
+
+        assert_eq!(sila.active_terminals(), get_terminals());
+    }
+
+    #[test]
+    fn test_command_line_short_circuits_per_terminal() {
+        let terminal = Terminal {
+            name: "tmp".to_string(),
+            path: std::env::temp_dir().to_string_lossy().to_string(),
+            ..Default::default()
+        };
+        let scenarios = vec![
+            (
+                "false && echo no || echo yes; echo done",
+                "yes\ndone\n",
+                Status::Exited(0),
+            ),
+            ("true || echo no", "", Status::Exited(0)),
+            ("echo a | grep b && echo no", "", Status::Exited(1)),
+        ];
+
+        for (input, stdout, status) in scenarios {
+            let line = CommandOption::from_input(input.to_string()).unwrap();
+            let output = run_command_line(
+                &terminal,
+                &line,
+                &Variables::default(),
+                None,
+                &Children::default(),
+                false,
+            );
+
+            assert_eq!(String::from_utf8(output.stdout).unwrap(), stdout);
+            assert_eq!(output.status, status);
+        }
+    }
+
+    #[test]
+    fn test_redirections_are_relative_to_the_terminal() {
+        let directory = TempDir::new("redirect");
+        let terminal = Terminal {
+            name: "tmp".to_string(),
+            path: directory.to_string_lossy().to_string(),
+            ..Default::default()
+        };
+
+        let input = "echo out > log; ls missing >> log 2>&1; tr a-z A-Z < log 2> err";
+        let line = CommandOption::from_input(input.to_string()).unwrap();
+        let output = run_command_line(
+            &terminal,
+            &line,
+            &Variables::default(),
+            None,
+            &Children::default(),
+            false,
+        );
+        let stdout = String::from_utf8(output.stdout).unwrap();
+
+        assert!(stdout.starts_with("OUT\nLS: "));
+        assert_eq!(std::fs::read(directory.join("err")).unwrap(), b"");
+        assert!(output.stages.iter().all(|stage| stage.stderr.is_empty()));
+    }
+
+    #[test]
+    fn test_detached_stdin_is_empty() {
+        let terminal = Terminal {
+            name: "tmp".to_string(),
+            path: std::env::temp_dir().to_string_lossy().to_string(),
+            ..Default::default()
+        };
+
+        let line = CommandOption::from_input("wc -c | tr -d ' '".to_string()).unwrap();
+        let output = run_command_line(
+            &terminal,
+            &line,
+            &Variables::default(),
+            None,
+            &Children::default(),
+            true,
+        );
+
+        assert_eq!(String::from_utf8(output.stdout).unwrap(), "0\n");
+        assert_eq!(output.status, Status::Exited(0));
+    }
+
+    #[test]
+    fn test_placeholders_are_expanded_per_terminal() {
+        let terminal = Terminal {
+            name: "T 1".to_string(),
+            path: std::env::temp_dir().to_string_lossy().to_string(),
+            ..Default::default()
+        };
+
+        let line = CommandOption::from_input("echo {name}.tgz '{name}'".to_string()).unwrap();
+        let variables = terminal.variables(0, &BTreeMap::new());
+        let output = run_command_line(
+            &terminal,
+            &line,
+            &variables,
+            None,
+            &Children::default(),
+            false,
+        );
+
+        assert_eq!(output.command, "echo 'T 1.tgz' '{name}'");
+        assert_eq!(
+            String::from_utf8(output.stdout).unwrap(),
+            "T 1.tgz {name}\n"
+        );
+    }
+
+    #[test]
+    fn test_pin_with_glob_and_negation() {
+        let mut sila = Runner {
+            all_terminals: get_terminals(),
+            ..Default::default()
+        };
+
+        sila.pin(vec!["T*".to_string(), "!T2".to_string()]);
+        assert_eq!(
+            sila.active_terminals(),
+            vec![Terminal::new("T1"), Terminal::new("T3")]
+        );
+
+        sila.unpin(vec!["/^T[13]$/".to_string()]);
+        assert_eq!(sila.pinned_terminals, HashSet::new());
+    }
+
+    #[test]
+    fn test_split_target_prefix() {
+        let scenarios = vec![
+            ("git status", None, "git status"),
+            (
+                "@PJ1,PJ3 git status",
+                Some(vec!["PJ1", "PJ3"]),
+                " git status",
+            ),
+            (
+                "  @backend cargo test",
+                Some(vec!["backend"]),
+                " cargo test",
+            ),
+            ("@@web,!PJ2, ls", Some(vec!["@web", "!PJ2"]), " ls"),
+            ("echo @PJ1", None, "echo @PJ1"),
+            ("@ pwd", Some(vec![]), " pwd"),
+            ("@, pwd", Some(vec![]), " pwd"),
+        ];
+
+        for (input, target, rest) in scenarios {
+            let target = target.map(|t| t.iter().map(|s| s.to_string()).collect::<Vec<_>>());
+            assert_eq!(split_target(input), (target, rest), "input: {}", input);
+        }
+    }
+
+    #[test]
+    fn test_reload_keeps_pins_and_bans_by_name() {
+        let dir = TempDir::new("reload");
+        let path = dir.join("sila.yaml");
+        let config = |names: &[&str]| {
+            let terminals = names
+                .iter()
+                .map(|name| format!("- name: {}\n  path: .\n", name))
+                .collect::<String>();
+            std::fs::write(&path, terminals).unwrap();
+        };
+
+        config(&["T1", "T2", "T3"]);
+        let mut sila = Runner::new(ConfigSource {
+            path: path.clone(),
+            ..Default::default()
+        })
+        .unwrap();
+        sila.pin(vec!["T1".to_string(), "T3".to_string()]);
+        sila.ban(vec!["T2".to_string()]);
+
+        config(&["T1", "T2", "T4"]);
+        sila.reload();
+
+        let names = |set: &HashSet<Terminal>| {
+            let mut names = set.iter().map(|t| t.name.clone()).collect::<Vec<_>>();
+            names.sort();
+            names
+        };
+        assert_eq!(sila.all_terminals.len(), 3);
+        assert_eq!(names(&sila.pinned_terminals), vec!["T1"]);
+        assert_eq!(names(&sila.banned_terminals), vec!["T2"]);
+
+        // a broken config keeps the terminals loaded before
+        std::fs::write(&path, "- name: [").unwrap();
+        sila.reload();
+        assert_eq!(sila.all_terminals.len(), 3);
+    }
+
+    #[test]
+    fn test_watch_reload_dropping_every_pin_skips_the_line() {
+        let dir = TempDir::new("watch");
+        let path = dir.join("sila.yaml");
+        std::fs::write(&path, "- name: T1\n  path: .\n- name: T2\n  path: .\n").unwrap();
+        let mut sila = Runner::new(ConfigSource {
+            path: path.clone(),
+            ..Default::default()
+        })
+        .unwrap();
+        sila.watch = true;
+        sila.pin(vec!["T1".to_string()]);
+
+        std::fs::write(&path, "- name: T2\n  path: .\n- name: T3\n  path: .\n").unwrap();
+        sila.modified = None;
+
+        assert_eq!(
+            sila.execute_line("touch ran".to_string()),
+            LineStatus::Failed
+        );
+        assert!(sila.pinned_terminals.is_empty());
+        assert!(!dir.join("ran").exists());
+
+        assert_eq!(sila.execute_line("touch ran".to_string()), LineStatus::Done);
+        assert!(dir.join("ran").exists());
+    }
+
+    #[test]
+    fn test_add_remove_and_save_terminals() {
+        let dir = TempDir::new("save");
+        let mut sila = Runner {
+            all_terminals: get_terminals(),
+            ..Default::default()
+        };
+
+        sila.add_terminal("extra", &dir.display().to_string());
+        sila.add_terminal("T1", &dir.display().to_string());
+        sila.add_terminal("missing", "/no/such/dir");
+        sila.pin(vec!["T2".to_string()]);
+        sila.remove_terminals(vec!["T2".to_string(), "T9".to_string()]);
+
+        let names = sila.all_terminals.iter().map(|t| t.name.as_str());
+        assert_eq!(names.collect::<Vec<_>>(), vec!["T1", "T3", "extra"]);
+        assert!(sila.pinned_terminals.is_empty());
+
+        let path = dir.join("saved.yaml");
+        sila.save(Some(&path.display().to_string()));
+
+        let saved = Config::load(&path).unwrap();
+        assert_eq!(saved.terminals, sila.all_terminals);
+    }
+
+    #[test]
+    fn test_save_keeps_the_config_as_written() {
+        let dir = TempDir::new("save-written");
+        for project in ["api", "old", "extra", "repos/found/.git"].iter() {
+            std::fs::create_dir_all(dir.join(project)).unwrap();
+        }
+        let path = dir.join("sila.yaml");
+        let written = "discover:\n  roots: [./repos]\nterminals:\n  - name: api\n    path: ./api\n  - name: old\n    path: ./old\n";
+        std::fs::write(&path, written).unwrap();
+
+        // the discovery flags of the command line are not saved
+        let mut sila = Runner::new(ConfigSource {
+            path: path.clone(),
+            roots: vec![dir.join("repos").display().to_string()],
+            exclude: vec!["x*".to_string()],
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(sila.all_terminals.len(), 3);
+
+        sila.shell.enabled = true;
+        sila.add_terminal("extra", &dir.join("extra").display().to_string());
+        sila.remove_terminals(vec!["old".to_string()]);
+        sila.save(None);
+
+        let saved = Config::load_as_written(&path).unwrap();
+        let terminals = saved
+            .terminals
+            .iter()
+            .map(|t| (t.name.as_str(), t.path.clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            terminals,
+            vec![
+                ("api", "./api".to_string()),
+                ("extra", dir.join("extra").display().to_string()),
+            ]
+        );
+        let discover = saved.discover.unwrap();
+        assert_eq!(discover.roots, vec!["./repos".to_string()]);
+        assert!(discover.exclude.is_empty());
+        assert!(!saved.shell.enabled);
+    }
+
+    #[test]
+    fn test_recall_records_the_expanded_lines() {
+        let mut sila = Runner::default();
+
+        assert_eq!(sila.recall("git status\n"), Some("git status".to_string()));
+        assert_eq!(sila.recall("!! -s"), Some("git status -s".to_string()));
+        assert_eq!(sila.recall("!!"), Some("git status -s".to_string()));
+        assert_eq!(sila.recall("!5"), None);
+        assert_eq!(sila.recall("  "), Some("".to_string()));
+        assert_eq!(sila.history, vec!["git status", "git status -s"]);
+    }
+
+    #[test]
+    fn test_interrupt_stops_the_running_line() {
+        let terminal = Terminal {
+            name: "T1".to_string(),
+            path: ".".to_string(),
+            ..Default::default()
+        };
+        let line = CommandOption::from_input("sleep 5 && echo never".to_string()).unwrap();
+        let children = Children::default();
+
+        let interrupter = children.clone();
+        std::thread::spawn(move || {
+            std::thread::sleep(std::time::Duration::from_millis(200));
+            interrupter.interrupt();
+        });
+
+        let started = std::time::Instant::now();
+        let output = run_command_line(
+            &terminal,
+            &line,
+            &Variables::default(),
+            None,
+            &children,
+            false,
+        );
+
+        assert!(started.elapsed() < std::time::Duration::from_secs(4));
+        assert_eq!(output.status, Status::Signaled(2));
+        assert!(output.stdout.is_empty());
+    }
+
+    #[test]
+    fn test_execute_line_reports_the_line_status() {
+        let mut sila = Runner {
+            all_terminals: vec![Terminal {
+                name: "T1".to_string(),
+                path: ".".to_string(),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        let scenarios = vec![
+            ("", LineStatus::Done),
+            ("true", LineStatus::Done),
+            ("true && false", LineStatus::Failed),
+            ("@T9 true", LineStatus::Failed),
+            ("@ true", LineStatus::Failed),
+            ("@, true", LineStatus::Failed),
+            ("echo 'unterminated", LineStatus::Failed),
+            ("list", LineStatus::Done),
+            ("exit", LineStatus::Exit),
+        ];
+
+        for (line, expected) in scenarios {
+            assert_eq!(
+                sila.execute_line(line.to_string()),
+                expected,
+                "line: {}",
+                line
+            );
+        }
+    }
+
+    #[test]
+    fn test_prompt_shows_the_selection() {
+        let mut sila = Runner {
+            all_terminals: get_terminals(),
+            ..Default::default()
+        };
+        assert_eq!(sila.prompt(), "[all 3]> ");
+
+        sila.ban(vec!["T2".to_string()]);
+        assert_eq!(sila.prompt(), "[2/3 active]> ");
+
+        sila.pin(vec!["T3".to_string(), "T1".to_string()]);
+        assert_eq!(sila.prompt(), "[pinned: T1,T3]> ");
+
+        sila.prompt = Some("{active}/{total} (pinned {pinned}, banned {banned}) $ ".to_string());
+        assert_eq!(sila.prompt(), "2/3 (pinned T1,T3, banned T2) $ ");
+
+        sila.all_terminals.push(Terminal::new("T4"));
+        sila.all_terminals.push(Terminal::new("T5"));
+        sila.pin(vec!["T*".to_string()]);
+        sila.prompt = None;
+        assert_eq!(sila.prompt(), "[pinned: T1,T2,T3,+2]> ");
+    }
 }
@@ -0,0 +1,280 @@
+use std::collections::BTreeMap;
+
+use regex::Regex;
+
+use crate::terminal::Terminal;
+
+/// Named lists of terminal names from the `groups` section of the config
+pub type Groups = BTreeMap<String, Vec<String>>;
+
+/// An argument of `pin`, `ban`, `unpin` and `unban` selecting terminals
+#[derive(Debug, Clone)]
+pub struct Selector {
+    /// The argument as typed, used in the messages
+    pub input: String,
+    pattern: Pattern,
+    /// `!pattern` removes the matching terminals from the selection
+    negated: bool,
+}
+
+#[derive(Debug, Clone)]
+enum Pattern {
+    /// A terminal name, or a group or tag name when no terminal is called like that
+    Name(String),
+    /// `@name` selects the members of the group and the terminals tagged with the name
+    Group(String),
+    /// A name with `*`, `?` or `[...]` wildcards
+    Glob(String),
+    /// `/regex/` matched against the terminal names
+    Regex(Regex),
+}
+
+/// The terminals picked by a list of selectors
+#[derive(Debug)]
+pub struct Selection<'a> {
+    pub terminals: Vec<&'a Terminal>,
+    /// Selectors that did not match any terminal
+    pub unmatched: Vec<String>,
+}
+
+impl Selector {
+    pub fn parse(input: &str) -> Result<Self, String> {
+        let (negated, pattern) = match input.strip_prefix('!') {
+            Some(pattern) => (true, pattern),
+            None => (false, input),
+        };
+
+        let pattern = if let Some(group) = pattern.strip_prefix('@') {
+            Pattern::Group(group.to_string())
+        } else if pattern.len() > 1 && pattern.starts_with('/') && pattern.ends_with('/') {
+            let regex = Regex::new(&pattern[1..pattern.len() - 1])
+                .map_err(|e| format!("invalid regex `{}`: {}", pattern, e))?;
+            Pattern::Regex(regex)
+        } else if pattern.contains(&['*', '?', '['][..]) {
+            Pattern::Glob(pattern.to_string())
+        } else {
+            Pattern::Name(pattern.to_string())
+        };
+
+        Ok(Selector {
+            input: input.to_string(),
+            pattern,
+            negated,
+        })
+    }
+
+    /// The terminals matching the selector, in the given order
+    pub fn select<'a>(&self, terminals: &'a [Terminal], groups: &Groups) -> Vec<&'a Terminal> {
+        let in_group = |name: &str, terminal: &Terminal| {
+            groups
+                .get(name)
+                .is_some_and(|members| members.contains(&terminal.name))
+        };
+        let filter = |predicate: &dyn Fn(&Terminal) -> bool| {
+            terminals
+                .iter()
+                .filter(|t| predicate(t))
+                .collect::<Vec<_>>()
+        };
+
+        match &self.pattern {
+            Pattern::Name(name) if terminals.iter().any(|t| t.name == *name) => {
+                filter(&|t| t.name == *name)
+            }
+            Pattern::Name(name) | Pattern::Group(name) => {
+                filter(&|t| in_group(name, t) || t.tags.contains(name))
+            }
+            Pattern::Glob(glob) => filter(&|t| matches_glob(glob, &t.name)),
+            Pattern::Regex(regex) => filter(&|t| regex.is_match(&t.name)),
+        }
+    }
+}
+
+/// Select the union of the terminals matching the selectors minus the ones matching
+/// the negated selectors, when there are only negated selectors all the terminals are
+/// the starting point so that `pin !legacy-*` pins everything but the legacy terminals
+pub fn resolve<'a>(
+    selectors: &[Selector],
+    terminals: &'a [Terminal],
+    groups: &Groups,
+) -> Selection<'a> {
+    let mut selected = vec![false; terminals.len()];
+    let mut unmatched = vec![];
+
+    if selectors.iter().all(|selector| selector.negated) {
+        selected = vec![true; terminals.len()];
+    }
+
+    // the positive selectors are applied first so the order of the arguments does not matter
+    let (negated, positive): (Vec<_>, Vec<_>) = selectors.iter().partition(|s| s.negated);
+
+    for selector in positive.into_iter().chain(negated) {
+        let matched = selector.select(terminals, groups);
+        if matched.is_empty() {
+            unmatched.push(selector.input.clone());
+        }
+
+        for terminal in matched {
+            let index = terminals.iter().position(|t| t == terminal).unwrap();
+            selected[index] = !selector.negated;
+        }
+    }
+
+    Selection {
+        terminals: terminals
+            .iter()
+            .zip(selected)
+            .filter(|(_, selected)| *selected)
+            .map(|(terminal, _)| terminal)
+            .collect(),
+        unmatched,
+    }
+}
+
+/// Check the name against a glob like `svc-*` or `api-v[12]`
+pub fn matches_glob(pattern: &str, name: &str) -> bool {
+    glob_matches(
+        &pattern.chars().collect::<Vec<_>>(),
+        &name.chars().collect::<Vec<_>>(),
+    )
+}
+
+/// Shell like wildcards: `*` any text, `?` any character, `[a-z]` or `[!a-z]` a character class
+fn glob_matches(pattern: &[char], name: &[char]) -> bool {
+    match pattern.split_first() {
+        None => name.is_empty(),
+        Some(('*', rest)) => (0..=name.len()).any(|skip| glob_matches(rest, &name[skip..])),
+        Some(('?', rest)) => !name.is_empty() && glob_matches(rest, &name[1..]),
+        Some(('[', rest)) if rest.contains(&']') => {
+            let end = rest.iter().position(|ch| *ch == ']').unwrap();
+            let (class, negated) = match rest[..end].split_first() {
+                Some(('!', class)) | Some(('^', class)) => (class, true),
+                _ => (&rest[..end], false),
+            };
+
+            match name.split_first() {
+                Some((ch, name_rest)) => {
+                    in_class(class, *ch) != negated && glob_matches(&rest[end + 1..], name_rest)
+                }
+                None => false,
+            }
+        }
+        Some((ch, rest)) => name.first() == Some(ch) && glob_matches(rest, &name[1..]),
+    }
+}
+
+fn in_class(class: &[char], ch: char) -> bool {
+    let mut index = 0;
+
+    while index < class.len() {
+        if index + 2 < class.len() && class[index + 1] == '-' {
+            if class[index] <= ch && ch <= class[index + 2] {
+                return true;
+            }
+            index += 3;
+        } else {
+            if class[index] == ch {
+                return true;
+            }
+            index += 1;
+        }
+    }
+
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn terminal(name: &str, tags: &[&str]) -> Terminal {
+        Terminal {
+            name: name.to_string(),
+            path: format!("/path/{}", name),
+            tags: tags.iter().map(|tag| tag.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    fn names(terminals: Vec<&Terminal>) -> Vec<&str> {
+        terminals.iter().map(|t| t.name.as_str()).collect()
+    }
+
+    #[test]
+    fn test_select_by_name_tag_and_group() {
+        let terminals = vec![
+            terminal("web", &["frontend"]),
+            terminal("api", &["backend"]),
+            terminal("db", &[]),
+            terminal("backend", &[]),
+        ];
+        let mut groups = Groups::new();
+        groups.insert(
+            "backend".to_string(),
+            vec!["db".to_string(), "api".to_string()],
+        );
+        groups.insert("infra".to_string(), vec!["db".to_string()]);
+
+        let scenarios = vec![
+            ("web", vec!["web"]),
+            ("@frontend", vec!["web"]),
+            ("@backend", vec!["api", "db"]),
+            // a terminal name wins over a group name
+            ("backend", vec!["backend"]),
+            ("infra", vec!["db"]),
+            ("frontend", vec!["web"]),
+            ("nothing", vec![]),
+            ("@nothing", vec![]),
+        ];
+
+        for (input, expected) in scenarios {
+            let selected = Selector::parse(input).unwrap().select(&terminals, &groups);
+            assert_eq!(names(selected), expected, "selector: {}", input);
+        }
+    }
+
+    #[test]
+    fn test_resolve_globs_regexes_and_negations() {
+        let terminals = vec![
+            terminal("svc-auth", &[]),
+            terminal("svc-legacy", &[]),
+            terminal("api-v1", &[]),
+            terminal("api-v2", &[]),
+            terminal("legacy-web", &[]),
+        ];
+
+        let scenarios = vec![
+            (vec!["svc-*"], vec!["svc-auth", "svc-legacy"], vec![]),
+            (vec!["/^api-.*$/"], vec!["api-v1", "api-v2"], vec![]),
+            (vec!["api-v[2-9]"], vec!["api-v2"], vec![]),
+            (
+                vec!["api-v[!2]", "svc-?uth"],
+                vec!["svc-auth", "api-v1"],
+                vec![],
+            ),
+            (
+                vec!["!*legacy*"],
+                vec!["svc-auth", "api-v1", "api-v2"],
+                vec![],
+            ),
+            (vec!["!svc-legacy", "svc-*"], vec!["svc-auth"], vec![]),
+            (vec!["db-*", "api-v1"], vec!["api-v1"], vec!["db-*"]),
+        ];
+
+        for (inputs, expected, unmatched) in scenarios {
+            let selectors = inputs
+                .iter()
+                .map(|input| Selector::parse(input).unwrap())
+                .collect::<Vec<_>>();
+            let selection = resolve(&selectors, &terminals, &Groups::new());
+
+            assert_eq!(names(selection.terminals), expected, "{:?}", inputs);
+            assert_eq!(selection.unmatched, unmatched, "{:?}", inputs);
+        }
+    }
+
+    #[test]
+    fn test_invalid_regex() {
+        assert!(Selector::parse("/api-(/").is_err());
+    }
+}
@@ -1,31 +1,517 @@
-use std::{io::BufReader, path::PathBuf};
+use std::{
+    collections::{BTreeMap, HashSet},
+    error::Error,
+    ffi::OsString,
+    fmt,
+    path::{Component, Path, PathBuf},
+    time::SystemTime,
+};
 
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, PartialEq, Serialize, Deserialize, Clone, Eq, Hash)]
+use crate::{discover::Discover, lexer::Variables, selector::Groups};
+
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone, Eq, Hash, Default)]
 
 pub struct Terminal {
     pub name: String,
     pub path: String,
+
+    /// Custom values available as `{key}` placeholders in the commands
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub vars: BTreeMap<String, String>,
+
+    /// Environment variables exported only in this terminal
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+
+    /// Labels to select the terminal with `@tag`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+}
+
+impl Terminal {
+    /// The placeholders and the environment of the terminal, `index` is its position in
+    /// the config file, the terminal env overrides the global one which overrides the process one
+    pub fn variables(&self, index: usize, global_env: &BTreeMap<String, String>) -> Variables {
+        let mut variables = Variables::default();
+
+        variables.env.extend(utf8_vars(std::env::vars_os()));
+        variables.env.extend(global_env.clone());
+        variables.env.extend(self.env.clone());
+        variables
+            .env
+            .insert("SILA_TERMINAL_NAME".to_string(), self.name.clone());
+        variables
+            .env
+            .insert("SILA_TERMINAL_PATH".to_string(), self.path.clone());
+
+        for (key, value) in self.vars.iter() {
+            variables.placeholders.insert(key.clone(), value.clone());
+        }
+
+        variables
+            .placeholders
+            .insert("name".to_string(), self.name.clone());
+        variables
+            .placeholders
+            .insert("path".to_string(), self.path.clone());
+        variables
+            .placeholders
+            .insert("index".to_string(), index.to_string());
+
+        variables
+    }
+}
+
+/// The variables that are valid UTF-8, the other ones are left out of the expansion
+/// and the children still inherit them untouched from the process
+fn utf8_vars<I>(vars: I) -> impl Iterator<Item = (String, String)>
+where
+    I: Iterator<Item = (OsString, OsString)>,
+{
+    vars.filter_map(|(key, value)| Some((key.into_string().ok()?, value.into_string().ok()?)))
+}
+
+/// Running the input lines through a shell instead of the built-in pipeline executor
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct Shell {
+    /// The shell called with `-c` like `sh`, `bash` or `zsh`
+    #[serde(default = "Shell::default_program")]
+    pub program: String,
+
+    /// Lines go through the shell only when enabled
+    #[serde(default)]
+    pub enabled: bool,
+}
+
+impl Shell {
+    fn default_program() -> String {
+        "sh".to_string()
+    }
+
+    fn is_default(&self) -> bool {
+        *self == Shell::default()
+    }
 }
 
-pub struct Config;
+impl Default for Shell {
+    fn default() -> Self {
+        Shell {
+            program: Shell::default_program(),
+            enabled: false,
+        }
+    }
+}
+
+/// Settings and terminals loaded from the config yaml file
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone, Default)]
+pub struct Config {
+    /// Environment variables exported in every terminal
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+
+    #[serde(default, skip_serializing_if = "Shell::is_default")]
+    pub shell: Shell,
+
+    /// Named lists of terminals to select them with `@group`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub groups: Groups,
+
+    /// Directory trees scanned for more terminals
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub discover: Option<Discover>,
+
+    /// Template of the prompt, see `Runner::prompt` for the placeholders
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prompt: Option<String>,
+
+    #[serde(default)]
+    pub terminals: Vec<Terminal>,
+}
 
 impl Config {
-    /// Load the terminals from config yaml file
-    pub fn load(path: PathBuf) -> Vec<Terminal> {
-        let input = std::fs::File::open(path).expect("Provided file does not exist");
-        let rdr = BufReader::new(input);
-        let terminals: Vec<Terminal> =
-            serde_yaml::from_reader(rdr).expect("Could not process the config.yaml file");
+    /// Load the config yaml file, it is either a list of terminals
+    /// or a map with the settings and a `terminals` list
+    pub fn load(path: &Path) -> Result<Config, ConfigError> {
+        let mut config = Config::load_as_written(path)?;
+        config.resolve_paths(path);
+
+        Ok(config)
+    }
+
+    /// Load the config yaml file keeping the paths as they are written, to save it back
+    pub fn load_as_written(path: &Path) -> Result<Config, ConfigError> {
+        let content = std::fs::read_to_string(path).map_err(|error| ConfigError::Io {
+            path: path.to_path_buf(),
+            error,
+        })?;
+
+        Config::parse(&content).map_err(|error| {
+            let mut message = error.to_string();
+
+            // the location is part of the message but it is printed next to the path
+            if let Some(location) = error.location() {
+                let suffix = format!(" at line {} column {}", location.line(), location.column());
+                if message.ends_with(&suffix) {
+                    message.truncate(message.len() - suffix.len());
+                }
+            }
+
+            ConfigError::Parse {
+                path: path.to_path_buf(),
+                line: error.location().map(|location| location.line()),
+                column: error.location().map(|location| location.column()),
+                message,
+            }
+        })
+    }
+
+    /// Make the terminal paths and the discover roots absolute, `~` and `$VAR` are
+    /// expanded and relative paths start from the directory of the config file
+    fn resolve_paths(&mut self, config_path: &Path) {
+        // made absolute first, the parent of a bare file name like `sila.yaml` is empty
+        let base = std::path::absolute(config_path)
+            .ok()
+            .and_then(|path| path.parent().map(Path::to_path_buf))
+            .unwrap_or_default();
+
+        for terminal in self.terminals.iter_mut() {
+            terminal.path = resolve_path(&terminal.path, &base);
+        }
+
+        if let Some(discover) = self.discover.as_mut() {
+            for root in discover.roots.iter_mut() {
+                *root = resolve_path(root, &base);
+            }
+        }
+    }
+
+    /// Add the discovered terminals and check that the terminals are usable,
+    /// `path` is the config file the problems are reported against
+    pub fn prepare(mut self, path: &Path) -> Result<Config, ConfigError> {
+        self.discover_terminals();
+
+        let problems = self.problems(false);
+        if !problems.is_empty() {
+            return Err(ConfigError::Invalid {
+                path: path.to_path_buf(),
+                problems,
+            });
+        }
+
+        Ok(self)
+    }
+
+    /// Add the discovered terminals and list the problems, including the ones
+    /// which do not prevent sila from starting like names unusable with `pin`
+    pub fn check(&mut self) -> Vec<Problem> {
+        self.discover_terminals();
+
+        self.problems(true)
+    }
+
+    fn problems(&self, lint: bool) -> Vec<Problem> {
+        let mut problems = vec![];
+        let mut names = HashSet::new();
+
+        let roots = self
+            .discover
+            .iter()
+            .flat_map(|discover| discover.roots.iter());
+        for root in roots {
+            if !Path::new(root).is_dir() {
+                problems.push(Problem::MissingRoot(root.clone()));
+            }
+        }
+
+        for terminal in self.terminals.iter() {
+            let name = terminal.name.clone();
+            let path = Path::new(&terminal.path);
+
+            if !names.insert(&terminal.name) {
+                problems.push(Problem::DuplicateName(name.clone()));
+            }
+
+            if lint && !is_selectable(&terminal.name) {
+                problems.push(Problem::UnselectableName(name.clone()));
+            }
+
+            if !path.exists() {
+                problems.push(Problem::MissingPath(name, terminal.path.clone()));
+            } else if !path.is_dir() {
+                problems.push(Problem::NotADirectory(name, terminal.path.clone()));
+            } else if lint && std::fs::read_dir(path).is_err() {
+                problems.push(Problem::NotReadable(name, terminal.path.clone()));
+            }
+        }
+
+        problems
+    }
+
+    /// Write the config as yaml
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        std::fs::write(path, serde_yaml::to_string(self)?)?;
+
+        Ok(())
+    }
+
+    /// Append the discovered terminals, skipping the projects already configured
+    fn discover_terminals(&mut self) {
+        let discovered = match &self.discover {
+            Some(discover) => discover.terminals(),
+            None => return,
+        };
+
+        for terminal in discovered {
+            let configured = self
+                .terminals
+                .iter()
+                .any(|t| t.path == terminal.path || t.name == terminal.name);
+
+            if !configured {
+                self.terminals.push(terminal);
+            }
+        }
+    }
+
+    fn parse(content: &str) -> Result<Config, serde_yaml::Error> {
+        let value: serde_yaml::Value = serde_yaml::from_str(content)?;
+
+        if value.is_sequence() {
+            return Ok(Config {
+                terminals: serde_yaml::from_str(content)?,
+                ..Default::default()
+            });
+        }
+
+        serde_yaml::from_str(content)
+    }
+}
+
+/// Expand `~` and `$VAR` and make the path absolute, relative paths start from `base`
+pub fn resolve_path(path: &str, base: &Path) -> String {
+    let expanded = expand_env(path);
+    let expanded = match expanded.strip_prefix('~') {
+        Some(rest) if rest.is_empty() || rest.starts_with('/') => {
+            std::env::var("HOME").unwrap_or_default() + rest
+        }
+        _ => expanded,
+    };
+
+    let mut resolved = PathBuf::new();
+    for component in base.join(expanded).components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                resolved.pop();
+            }
+            component => resolved.push(component),
+        }
+    }
+
+    resolved.to_string_lossy().to_string()
+}
+
+/// Replace `$VAR` and `${VAR}` with the value of the environment variable,
+/// the unknown variables are left as they are
+fn expand_env(path: &str) -> String {
+    let mut expanded = String::new();
+    let mut rest = path;
+
+    while let Some(start) = rest.find('$') {
+        expanded.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+
+        let (name, len) = match after.strip_prefix('{') {
+            Some(braced) => match braced.find('}') {
+                Some(end) => (&braced[..end], end + 2),
+                None => ("", 0),
+            },
+            None => {
+                let end = after
+                    .find(|ch: char| !(ch.is_ascii_alphanumeric() || ch == '_'))
+                    .unwrap_or(after.len());
+                (&after[..end], end)
+            }
+        };
+
+        match std::env::var(name) {
+            Ok(value) if !name.is_empty() => expanded.push_str(&value),
+            _ => expanded.push_str(&rest[start..start + 1 + len]),
+        }
+        rest = &after[len..];
+    }
 
-        terminals
+    expanded.push_str(rest);
+    expanded
+}
+
+/// Where the config comes from, kept around to load it again on `reload`
+#[derive(Debug, Clone, Default)]
+pub struct ConfigSource {
+    pub path: PathBuf,
+
+    /// Roots discovered on top of the `discover` section, the config file is optional when set
+    pub roots: Vec<String>,
+    pub markers: Vec<String>,
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+    pub max_depth: Option<usize>,
+}
+
+impl ConfigSource {
+    /// Read the config file and merge the discovery settings into its `discover` section
+    pub fn read(&self) -> Result<Config, ConfigError> {
+        let mut config = if self.roots.is_empty() || self.path.exists() {
+            Config::load(&self.path)?
+        } else {
+            Config::default()
+        };
+
+        if !self.roots.is_empty() {
+            let discover = config.discover.get_or_insert_with(Discover::default);
+
+            discover.roots.extend(self.roots.iter().cloned());
+            discover.include.extend(self.include.iter().cloned());
+            discover.exclude.extend(self.exclude.iter().cloned());
+
+            if !self.markers.is_empty() {
+                discover.markers = self.markers.clone();
+            }
+            if let Some(max_depth) = self.max_depth {
+                discover.max_depth = max_depth;
+            }
+        }
+
+        Ok(config)
+    }
+
+    /// The config file as it is written, the discovery settings of the command line
+    /// are left out, empty when there is no config file
+    pub fn read_as_written(&self) -> Result<Config, ConfigError> {
+        if self.path.exists() {
+            Config::load_as_written(&self.path)
+        } else {
+            Ok(Config::default())
+        }
+    }
+
+    /// Read the config and check that it can be used
+    pub fn load(&self) -> Result<Config, ConfigError> {
+        self.read()?.prepare(&self.path)
+    }
+
+    /// Last modification time of the config file, used to watch it for changes
+    pub fn modified(&self) -> Option<SystemTime> {
+        std::fs::metadata(&self.path)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+    }
+}
+
+/// A name `pin` and `ban` can select, the arguments are split on whitespace,
+/// the target prefix on commas and a leading `@` or `!` changes the meaning
+pub fn is_selectable(name: &str) -> bool {
+    !name.is_empty()
+        && !name.contains(|ch: char| ch.is_whitespace() || ch == ',')
+        && !name.starts_with(&['@', '!'][..])
+}
+
+/// Why the config could not be loaded
+#[derive(Debug)]
+pub enum ConfigError {
+    Io {
+        path: PathBuf,
+        error: std::io::Error,
+    },
+    /// The yaml is malformed or does not match the config layout
+    Parse {
+        path: PathBuf,
+        line: Option<usize>,
+        column: Option<usize>,
+        message: String,
+    },
+    /// The config is well formed but the terminals can not be used
+    Invalid {
+        path: PathBuf,
+        problems: Vec<Problem>,
+    },
+}
+
+/// A single issue found in the terminals of a config
+#[derive(Debug, PartialEq)]
+pub enum Problem {
+    DuplicateName(String),
+    /// The terminal name and its path
+    MissingPath(String, String),
+    NotADirectory(String, String),
+    NotReadable(String, String),
+    /// A name which can not be used with `pin` and `ban`
+    UnselectableName(String),
+    /// A `discover` root which is not a directory
+    MissingRoot(String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io { path, error } => {
+                write!(f, "could not read {}: {}", path.display(), error)
+            }
+            ConfigError::Parse {
+                path,
+                line: Some(line),
+                column: Some(column),
+                message,
+            } => write!(f, "{}:{}:{}: {}", path.display(), line, column, message),
+            ConfigError::Parse { path, message, .. } => {
+                write!(f, "{}: {}", path.display(), message)
+            }
+            ConfigError::Invalid { path, problems } => {
+                write!(f, "{} is not valid:", path.display())?;
+
+                for problem in problems {
+                    write!(f, "\n  - {}", problem)?;
+                }
+
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Error for ConfigError {}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Problem::DuplicateName(name) => write!(f, "terminal `{}` is defined twice", name),
+            Problem::MissingPath(name, path) => {
+                write!(f, "terminal `{}`: {} does not exist", name, path)
+            }
+            Problem::NotADirectory(name, path) => {
+                write!(f, "terminal `{}`: {} is not a directory", name, path)
+            }
+            Problem::NotReadable(name, path) => {
+                write!(f, "terminal `{}`: {} is not readable", name, path)
+            }
+            Problem::UnselectableName(name) => write!(
+                f,
+                "terminal name `{}` can not be used with pin and ban, avoid whitespace, commas and a leading @ or !",
+                name
+            ),
+            Problem::MissingRoot(root) => {
+                write!(f, "discover root {} is not a directory", root)
+            }
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_utils::TempDir;
     use std::{path::PathBuf, str::FromStr};
 
     #[test]
@@ -36,14 +522,215 @@ mod tests {
             Terminal {
                 name: "sila".to_string(),
                 path: "/home/alxolr/Work/cli-tools/sila".to_string(),
+                ..Default::default()
             },
             Terminal {
                 name: "search".to_string(),
                 path: "/home/alxolr/Work/cli-tools/search".to_string(),
+                ..Default::default()
             },
         ];
 
-        let terminals = Config::load(file);
-        assert_eq!(terminals, expected_terminals);
+        let config = Config::load(&file).unwrap();
+        assert_eq!(config.terminals, expected_terminals);
+    }
+
+    #[test]
+    fn custom_variables_are_placeholders() {
+        let terminals: Vec<Terminal> = serde_yaml::from_str(
+            "- name: api\n  path: /work/api\n  vars:\n    port: 3000\n    name: ignored\n",
+        )
+        .unwrap();
+
+        let placeholders = terminals[0].variables(2, &BTreeMap::new()).placeholders;
+
+        assert_eq!(placeholders["port"], "3000");
+        assert_eq!(placeholders["name"], "api");
+        assert_eq!(placeholders["path"], "/work/api");
+        assert_eq!(placeholders["index"], "2");
+    }
+
+    #[test]
+    fn terminal_env_overrides_global_env() {
+        let config = Config::parse(
+            "env:\n  STAGE: dev\n  TOKEN: abc\nterminals:\n  - name: api\n    path: /work/api\n    env:\n      STAGE: prod\n",
+        )
+        .unwrap();
+
+        let env = config.terminals[0].variables(0, &config.env).env;
+
+        assert_eq!(env["STAGE"], "prod");
+        assert_eq!(env["TOKEN"], "abc");
+        assert_eq!(env["SILA_TERMINAL_NAME"], "api");
+        assert_eq!(env["SILA_TERMINAL_PATH"], "/work/api");
+        assert!(env.contains_key("PATH"));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn non_utf8_vars_are_skipped() {
+        use std::os::unix::ffi::OsStringExt;
+
+        let vars = vec![
+            (OsString::from("STAGE"), OsString::from("dev")),
+            (
+                OsString::from("NAME"),
+                OsString::from_vec(b"caf\xe9".to_vec()),
+            ),
+            (
+                OsString::from_vec(b"K\xe9Y".to_vec()),
+                OsString::from("value"),
+            ),
+        ];
+
+        assert_eq!(
+            utf8_vars(vars.into_iter()).collect::<Vec<_>>(),
+            vec![("STAGE".to_string(), "dev".to_string())]
+        );
+    }
+
+    #[test]
+    fn shell_defaults_to_sh_and_disabled() {
+        let config = Config::parse("terminals: []").unwrap();
+        assert_eq!(config.shell, Shell::default());
+
+        let config =
+            Config::parse("shell:\n  program: bash\n  enabled: true\nterminals: []").unwrap();
+        assert_eq!(config.shell.program, "bash");
+        assert!(config.shell.enabled);
+
+        let config = Config::parse("shell:\n  enabled: true\nterminals: []").unwrap();
+        assert_eq!(config.shell.program, "sh");
+    }
+
+    #[test]
+    fn parsing_tags_and_groups() {
+        let config = Config::parse(
+            "groups:\n  backend: [api, db]\nterminals:\n  - name: api\n    path: /work/api\n    tags: [rust, backend]\n",
+        )
+        .unwrap();
+
+        assert_eq!(config.groups["backend"], vec!["api", "db"]);
+        assert_eq!(config.terminals[0].tags, vec!["rust", "backend"]);
+    }
+
+    #[test]
+    fn load_reports_missing_files_and_yaml_locations() {
+        let error = Config::load(Path::new("/no/such/sila.yaml")).unwrap_err();
+        assert!(matches!(error, ConfigError::Io { .. }));
+        assert!(error
+            .to_string()
+            .starts_with("could not read /no/such/sila.yaml: "));
+
+        let dir = TempDir::new("broken");
+        let path = dir.join("sila.yaml");
+        std::fs::write(&path, "terminals:\n  - name: api\n    path: [\n").unwrap();
+
+        let error = Config::load(&path).unwrap_err();
+        assert!(
+            matches!(error, ConfigError::Parse { line: Some(4), .. }),
+            "{:?}",
+            error
+        );
+        assert!(error
+            .to_string()
+            .starts_with(&format!("{}:4:", path.display())));
+    }
+
+    #[test]
+    fn prepare_lists_every_invalid_terminal() {
+        let dir = TempDir::new("problems");
+        let file = dir.join("file");
+        std::fs::write(&file, "").unwrap();
+
+        let config = Config::parse(&format!(
+            "- name: api\n  path: {dir}\n- name: api\n  path: {dir}\n- name: gone\n  path: /no/such/dir\n- name: file\n  path: {file}\n",
+            dir = dir.display(),
+            file = file.display(),
+        ))
+        .unwrap();
+
+        let problems = match config.prepare(Path::new("sila.yaml")) {
+            Err(ConfigError::Invalid { problems, .. }) => problems,
+            other => panic!("expected invalid config, got {:?}", other),
+        };
+
+        assert_eq!(
+            problems,
+            vec![
+                Problem::DuplicateName("api".to_string()),
+                Problem::MissingPath("gone".to_string(), "/no/such/dir".to_string()),
+                Problem::NotADirectory("file".to_string(), file.display().to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn check_lints_names_unusable_with_pin() {
+        let dir = std::env::temp_dir();
+        let mut config = Config::parse(&format!(
+            "- name: my api\n  path: {dir}\n- name: '@web'\n  path: {dir}\n- name: a,b\n  path: {dir}\n- name: db\n  path: {dir}\n",
+            dir = dir.display(),
+        ))
+        .unwrap();
+
+        assert!(config.clone().prepare(Path::new("sila.yaml")).is_ok());
+        assert_eq!(
+            config.check(),
+            vec![
+                Problem::UnselectableName("my api".to_string()),
+                Problem::UnselectableName("@web".to_string()),
+                Problem::UnselectableName("a,b".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn load_resolves_paths_from_the_config_directory() {
+        let dir = TempDir::new("relative");
+        let path = dir.join("sila.yaml");
+        std::fs::write(
+            &path,
+            "discover:\n  roots: [./repos]\nterminals:\n  - name: api\n    path: ./services/api\n  - name: web\n    path: ../web\n  - name: home\n    path: ~/work\n  - name: env\n    path: ${HOME}/$HOME/$NO_SUCH_SILA_VAR\n  - name: abs\n    path: /srv/abs\n",
+        )
+        .unwrap();
+
+        let config = Config::load(&path).unwrap();
+        let home = std::env::var("HOME").unwrap();
+        let paths = config.terminals.iter().map(|t| t.path.clone());
+
+        assert_eq!(
+            paths.collect::<Vec<_>>(),
+            vec![
+                dir.join("services/api").display().to_string(),
+                std::env::temp_dir().join("web").display().to_string(),
+                format!("{}/work", home),
+                format!(
+                    "{}/{}/$NO_SUCH_SILA_VAR",
+                    home,
+                    home.trim_start_matches('/')
+                ),
+                "/srv/abs".to_string(),
+            ]
+        );
+        assert_eq!(
+            config.discover.unwrap().roots,
+            vec![dir.join("repos").display().to_string()]
+        );
+    }
+
+    #[test]
+    fn bare_config_file_name_resolves_from_the_current_directory() {
+        let mut config = Config::parse("- name: api\n  path: ./services/api\n").unwrap();
+        config.resolve_paths(Path::new("sila.yaml"));
+
+        assert_eq!(
+            config.terminals[0].path,
+            std::env::current_dir()
+                .unwrap()
+                .join("services/api")
+                .display()
+                .to_string()
+        );
     }
 }